@@ -88,6 +88,49 @@ fn bench_decode_unsafe(c: &mut Criterion) {
     group.finish();
 }
 
+/// Benchmarks the encoding speed of the parallel COBS encoder.
+#[cfg(feature = "rayon")]
+fn bench_par_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("par_encode");
+
+    for size in [262144, 1048576, 4194304] {
+        let data: Vec<u8> = rand::rng().random_iter().take(size).collect();
+        let mut buffer = vec![0u8; encode_buffer(size)];
+
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| {
+                darkbio_cobs::par_encode(data, &mut buffer).unwrap();
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Benchmarks the decoding speed of the parallel COBS decoder.
+#[cfg(feature = "rayon")]
+fn bench_par_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("par_decode");
+
+    for size in [262144, 1048576, 4194304] {
+        let data: Vec<u8> = rand::rng().random_iter().take(size).collect();
+        let mut encoded = vec![0u8; encode_buffer(size)];
+
+        let len = encode(&data, &mut encoded).unwrap();
+        encoded.truncate(len);
+
+        let mut buffer = vec![0u8; decode_buffer(encoded.len())];
+
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &encoded, |b, encoded| {
+            b.iter(|| {
+                darkbio_cobs::par_decode(encoded, &mut buffer).unwrap();
+            });
+        });
+    }
+    group.finish();
+}
+
 /// Benchmarks the encoding speed of the jamesmunns/cobs encoder.
 fn bench_jamesmunns_encode(c: &mut Criterion) {
     let mut group = c.benchmark_group("jamesmunns/encode");
@@ -139,9 +182,14 @@ criterion_group!(
     bench_jamesmunns_decode
 );
 
+#[cfg(feature = "rayon")]
+criterion_group!(par_benches, bench_par_encode, bench_par_decode);
+
 fn main() {
     print_system_infos();
     benches();
+    #[cfg(feature = "rayon")]
+    par_benches();
     Criterion::default().configure_from_args().final_summary();
 }
 
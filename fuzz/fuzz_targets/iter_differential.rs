@@ -0,0 +1,42 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+#![no_main]
+
+use darkbio_cobs::{decode, decode_buffer, decode_iter, encode, encode_buffer, encode_iter};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Encoding lazily must match the slice encoder byte for byte
+    let mut enc_buf = vec![0u8; encode_buffer(data.len())];
+    let enc_len = encode(data, &mut enc_buf).unwrap();
+
+    let lazy: Vec<u8> = encode_iter(data.iter().copied()).collect();
+    assert_eq!(
+        lazy,
+        &enc_buf[..enc_len],
+        "encode mismatch for input {:?}",
+        data
+    );
+
+    // Decoding lazily must agree with the slice decoder on success and failure
+    let mut dec_buf = vec![0u8; decode_buffer(data.len())];
+    let slice_result = decode(data, &mut dec_buf);
+    let lazy_result: Result<Vec<u8>, _> = decode_iter(data.iter().copied()).collect();
+
+    match (&slice_result, &lazy_result) {
+        (Ok(len), Ok(lazy)) => {
+            assert_eq!(
+                &dec_buf[..*len],
+                &lazy[..],
+                "decode mismatch for input {:?}",
+                data
+            );
+        }
+        (Err(_), Err(_)) => {}
+        _ => panic!(
+            "decoders disagree for input {:?}: slice {:?}, lazy {:?}",
+            data, slice_result, lazy_result
+        ),
+    }
+});
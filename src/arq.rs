@@ -0,0 +1,519 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Reliable, in-order, exactly-once delivery over lossy COBS links.
+//!
+//! A [`Link`] is a sans-IO selective repeat ARQ endpoint. Outgoing messages are
+//! numbered and kept until acknowledged, retransmitted after a timeout, and only
+//! a window of them is in flight at any time. Incoming messages are buffered
+//! until the gaps before them are filled, acknowledged with a cumulative number
+//! and a bitmap of the ones received beyond it, and duplicates are suppressed.
+//!
+//! Every frame carries a CRC-32C, so corrupted frames are dropped and recovered
+//! by retransmission. The link never touches a transport or a timer itself: the
+//! caller moves frames between [`poll_transmit`](Link::poll_transmit) and
+//! [`receive`](Link::receive), and time is read from an injected [`Clock`].
+//!
+//! Wire format of the frames before the checksum and stuffing:
+//! - Data: `0x00`, sequence number (u16 BE), payload
+//! - Ack: `0x01`, next expected sequence number (u16 BE), bitmap (u32 BE)
+
+use crate::crc::{Crc32, decode_checked, encode_buffer_checked, encode_checked};
+use crate::decode_buffer;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Type byte of a frame carrying a message.
+const KIND_DATA: u8 = 0x00;
+
+/// Type byte of a frame carrying an acknowledgement.
+const KIND_ACK: u8 = 0x01;
+
+/// Number of sequence numbers beyond the cumulative one an ack reports on.
+const ACK_BITMAP: u16 = 32;
+
+/// Source of monotonic time for a [`Link`], measured from an arbitrary epoch.
+pub trait Clock {
+    /// Returns the time elapsed since the clock's epoch.
+    fn now(&self) -> Duration;
+}
+
+/// Clock backed by [`Instant`], with the epoch at its creation.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    /// Creates a clock starting at zero now.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock that only moves when told to. Clones share the same time, so a test
+/// can hold on to one and advance the links using the others.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// Creates a clock stopped at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward.
+    pub fn advance(&self, by: Duration) {
+        self.nanos
+            .fetch_add(by.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// Tunables of a [`Link`]. Both ends must use the same window size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArqConfig {
+    /// Maximum number of unacknowledged messages in flight, within `1..=32768`.
+    pub window: u16,
+    /// Time to wait for an acknowledgement before retransmitting.
+    pub timeout: Duration,
+    /// Number of retransmissions of a message before the link is declared dead.
+    pub max_retries: u32,
+    /// Maximum payload size of a message.
+    pub max_payload: usize,
+}
+
+impl Default for ArqConfig {
+    fn default() -> Self {
+        Self {
+            window: 16,
+            timeout: Duration::from_millis(200),
+            max_retries: 10,
+            max_payload: 1024,
+        }
+    }
+}
+
+/// Error types that can be returned from a reliable link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ArqError {
+    #[error("payload too large: have {size} bytes, max {max} bytes")]
+    TooLarge { size: usize, max: usize },
+    #[error("message {seq} unacknowledged after {retries} retransmissions")]
+    RetriesExhausted { seq: u16, retries: u32 },
+}
+
+/// Counters of the traffic passed through a [`Link`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArqStats {
+    /// Data frames transmitted for the first time.
+    pub sent: u64,
+    /// Data frames transmitted again after a timeout.
+    pub retransmitted: u64,
+    /// Messages delivered to the application.
+    pub delivered: u64,
+    /// Data frames received that were already received before.
+    pub duplicates: u64,
+    /// Frames dropped due to a broken encoding, checksum or layout.
+    pub corrupted: u64,
+}
+
+/// Message sent but not yet acknowledged.
+#[derive(Debug)]
+struct Inflight {
+    seq: u16,
+    frame: Vec<u8>,
+    deadline: Duration,
+    retries: u32,
+    acked: bool,
+}
+
+/// Sans-IO endpoint of a reliable link.
+#[derive(Debug)]
+pub struct Link<C: Clock = SystemClock> {
+    config: ArqConfig,
+    clock: C,
+
+    backlog: VecDeque<Vec<u8>>, // Messages waiting for room in the window
+    inflight: VecDeque<Inflight>, // Messages in the window, oldest first
+    tx_next: u16,               // Sequence number of the next new message
+
+    rx_next: u16,                         // Next sequence number to deliver
+    rx_window: VecDeque<Option<Vec<u8>>>, // Out of order messages, from rx_next on
+    delivered: VecDeque<Vec<u8>>,         // Messages ready for the application
+    ack_pending: bool,                    // Whether an ack needs to be sent
+
+    stats: ArqStats,
+}
+
+impl<C: Clock> Link<C> {
+    /// Creates a link endpoint with the given tunables and time source.
+    ///
+    /// # Panics
+    /// Panics if the window is not within `1..=32768`.
+    pub fn new(config: ArqConfig, clock: C) -> Self {
+        assert!(
+            (1..=32768).contains(&config.window),
+            "window {} out of range",
+            config.window
+        );
+        Self {
+            config,
+            clock,
+            backlog: VecDeque::new(),
+            inflight: VecDeque::new(),
+            tx_next: 0,
+            rx_next: 0,
+            rx_window: (0..config.window).map(|_| None).collect(),
+            delivered: VecDeque::new(),
+            ack_pending: false,
+            stats: ArqStats::default(),
+        }
+    }
+
+    /// Returns the statistics gathered by the link so far.
+    #[inline]
+    pub fn stats(&self) -> ArqStats {
+        self.stats
+    }
+
+    /// Queues a message for reliable delivery.
+    pub fn send(&mut self, payload: &[u8]) -> Result<(), ArqError> {
+        if payload.len() > self.config.max_payload {
+            return Err(ArqError::TooLarge {
+                size: payload.len(),
+                max: self.config.max_payload,
+            });
+        }
+        self.backlog.push_back(payload.to_vec());
+        Ok(())
+    }
+
+    /// Returns the number of messages not yet acknowledged by the remote end,
+    /// including the ones still waiting for room in the window.
+    pub fn unacked(&self) -> usize {
+        self.backlog.len() + self.inflight.iter().filter(|m| !m.acked).count()
+    }
+
+    /// Retrieves the next message delivered in order by the remote end.
+    pub fn recv(&mut self) -> Option<Vec<u8>> {
+        self.delivered.pop_front()
+    }
+
+    /// Returns the next encoded frame to put on the wire (without the 0x00
+    /// delimiter), or `None` if there's nothing to send right now. Acks go out
+    /// first, then expired retransmissions, then new messages as the window
+    /// allows.
+    ///
+    /// Returns an error if a message ran out of retransmissions, after which the
+    /// link should be considered dead.
+    pub fn poll_transmit(&mut self) -> Result<Option<Vec<u8>>, ArqError> {
+        if self.ack_pending {
+            self.ack_pending = false;
+
+            let mut bitmap = 0u32;
+            for (i, slot) in self
+                .rx_window
+                .iter()
+                .skip(1)
+                .take(ACK_BITMAP as usize)
+                .enumerate()
+            {
+                if slot.is_some() {
+                    bitmap |= 1 << i;
+                }
+            }
+            let mut body = [0u8; 7];
+            body[0] = KIND_ACK;
+            body[1..3].copy_from_slice(&self.rx_next.to_be_bytes());
+            body[3..7].copy_from_slice(&bitmap.to_be_bytes());
+            return Ok(Some(seal(&body)));
+        }
+        let now = self.clock.now();
+        for msg in self.inflight.iter_mut().filter(|m| !m.acked) {
+            if msg.deadline > now {
+                continue;
+            }
+            if msg.retries == self.config.max_retries {
+                return Err(ArqError::RetriesExhausted {
+                    seq: msg.seq,
+                    retries: msg.retries,
+                });
+            }
+            msg.retries += 1;
+            msg.deadline = now + self.config.timeout;
+            self.stats.retransmitted += 1;
+            return Ok(Some(msg.frame.clone()));
+        }
+        if self.inflight.len() < self.config.window as usize
+            && let Some(payload) = self.backlog.pop_front()
+        {
+            let mut body = Vec::with_capacity(payload.len() + 3);
+            body.push(KIND_DATA);
+            body.extend_from_slice(&self.tx_next.to_be_bytes());
+            body.extend_from_slice(&payload);
+
+            let frame = seal(&body);
+            self.inflight.push_back(Inflight {
+                seq: self.tx_next,
+                frame: frame.clone(),
+                deadline: now + self.config.timeout,
+                retries: 0,
+                acked: false,
+            });
+            self.tx_next = self.tx_next.wrapping_add(1);
+            self.stats.sent += 1;
+            return Ok(Some(frame));
+        }
+        Ok(None)
+    }
+
+    /// Returns the earliest time (on the link's clock) at which a retransmission
+    /// becomes due, or `None` if nothing is waiting for an acknowledgement. The
+    /// caller should call [`poll_transmit`](Self::poll_transmit) again by then.
+    pub fn next_timeout(&self) -> Option<Duration> {
+        self.inflight
+            .iter()
+            .filter(|m| !m.acked)
+            .map(|m| m.deadline)
+            .min()
+    }
+
+    /// Processes an encoded frame (without the 0x00 delimiter) arriving from the
+    /// remote end. Corrupted or malformed frames are silently dropped.
+    pub fn receive(&mut self, frame: &[u8]) {
+        let mut body = vec![0u8; decode_buffer(frame.len())];
+        let len = match decode_checked(frame, Crc32::CASTAGNOLI, &mut body) {
+            Ok(len) => len,
+            Err(_) => {
+                self.stats.corrupted += 1;
+                return;
+            }
+        };
+        match &body[..len] {
+            [KIND_DATA, s0, s1, payload @ ..] if payload.len() <= self.config.max_payload => {
+                self.receive_data(u16::from_be_bytes([*s0, *s1]), payload);
+            }
+            [KIND_ACK, a0, a1, b0, b1, b2, b3] => {
+                let bitmap = u32::from_be_bytes([*b0, *b1, *b2, *b3]);
+                self.receive_ack(u16::from_be_bytes([*a0, *a1]), bitmap);
+            }
+            _ => self.stats.corrupted += 1,
+        }
+    }
+
+    // Buffers an incoming message and delivers whatever became contiguous
+    fn receive_data(&mut self, seq: u16, payload: &[u8]) {
+        // Whatever arrived, the sender needs to hear about it (a duplicate means
+        // a previous ack was probably lost)
+        self.ack_pending = true;
+
+        let offset = seq.wrapping_sub(self.rx_next) as usize;
+        if offset >= self.config.window as usize {
+            // Behind the window, already delivered
+            self.stats.duplicates += 1;
+            return;
+        }
+        let slot = &mut self.rx_window[offset];
+        if slot.is_some() {
+            self.stats.duplicates += 1;
+            return;
+        }
+        *slot = Some(payload.to_vec());
+
+        while let Some(Some(_)) = self.rx_window.front() {
+            let msg = self.rx_window.pop_front().flatten().unwrap_or_default();
+            self.rx_window.push_back(None);
+            self.delivered.push_back(msg);
+            self.rx_next = self.rx_next.wrapping_add(1);
+            self.stats.delivered += 1;
+        }
+    }
+
+    // Marks messages as acknowledged and slides the window past them
+    fn receive_ack(&mut self, next: u16, bitmap: u32) {
+        let Some(base) = self.inflight.front().map(|m| m.seq) else {
+            return;
+        };
+        // Ignore stale acks from before the window and bogus ones beyond it
+        let cumulative = next.wrapping_sub(base) as usize;
+        if cumulative > self.inflight.len() {
+            return;
+        }
+        for (i, msg) in self.inflight.iter_mut().enumerate() {
+            let beyond = i.wrapping_sub(cumulative + 1);
+            if i < cumulative || (beyond < ACK_BITMAP as usize && bitmap & (1 << beyond) != 0) {
+                msg.acked = true;
+            }
+        }
+        while self.inflight.front().is_some_and(|m| m.acked) {
+            self.inflight.pop_front();
+        }
+    }
+}
+
+// Appends a CRC-32C to a frame body and stuffs it, shared with the other sans-IO
+// protocols of the crate
+pub(crate) fn seal(body: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0u8; encode_buffer_checked::<Crc32>(body.len())];
+    let len = encode_checked(body, Crc32::CASTAGNOLI, &mut frame)
+        .expect("buffer sized by encode_buffer_checked");
+    frame.truncate(len);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // Moves all the pending frames from one link to the other through a lossy
+    // channel that drops, corrupts, duplicates and reorders them
+    fn pump(from: &mut Link<ManualClock>, to: &mut Link<ManualClock>, rng: &mut StdRng, loss: f64) {
+        let mut wire = Vec::new();
+        while let Some(mut frame) = from.poll_transmit().unwrap() {
+            if rng.random_bool(loss) {
+                continue;
+            }
+            if rng.random_bool(loss) {
+                let at = rng.random_range(0..frame.len());
+                frame[at] ^= rng.random_range(1..=255);
+            }
+            if rng.random_bool(loss) {
+                wire.push(frame.clone());
+            }
+            wire.push(frame);
+        }
+        if wire.len() > 1 && rng.random_bool(loss) {
+            let (a, b) = (
+                rng.random_range(0..wire.len()),
+                rng.random_range(0..wire.len()),
+            );
+            wire.swap(a, b);
+        }
+        for frame in wire {
+            to.receive(&frame);
+        }
+    }
+
+    #[test]
+    fn test_arq_lossless() {
+        let clock = ManualClock::new();
+        let mut a = Link::new(ArqConfig::default(), clock.clone());
+        let mut b = Link::new(ArqConfig::default(), clock.clone());
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for i in 0..100u8 {
+            a.send(&[i, 0, i]).unwrap();
+        }
+        while a.unacked() > 0 {
+            pump(&mut a, &mut b, &mut rng, 0.0);
+            pump(&mut b, &mut a, &mut rng, 0.0);
+        }
+        for i in 0..100u8 {
+            assert_eq!(b.recv().unwrap(), [i, 0, i]);
+        }
+        assert_eq!(b.recv(), None);
+        assert_eq!(a.stats().retransmitted, 0);
+        assert_eq!(b.stats().duplicates, 0);
+    }
+
+    #[test]
+    fn test_arq_lossy_channel() {
+        let config = ArqConfig {
+            max_retries: 100,
+            ..Default::default()
+        };
+        for seed in 0..3 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let clock = ManualClock::new();
+            let mut a = Link::new(config, clock.clone());
+            let mut b = Link::new(config, clock.clone());
+
+            // Send enough messages both ways to wrap the sequence numbers
+            let count = 133000;
+            let mut want_a = Vec::new();
+            let mut want_b = Vec::new();
+            for i in 0..count {
+                let msg = (i as u32).to_be_bytes().to_vec();
+                if i % 2 == 0 {
+                    a.send(&msg).unwrap();
+                    want_b.push(msg);
+                } else {
+                    b.send(&msg).unwrap();
+                    want_a.push(msg);
+                }
+            }
+            let mut have_a = Vec::new();
+            let mut have_b = Vec::new();
+            while a.unacked() > 0 || b.unacked() > 0 {
+                pump(&mut a, &mut b, &mut rng, 0.1);
+                pump(&mut b, &mut a, &mut rng, 0.1);
+                have_a.extend(std::iter::from_fn(|| a.recv()));
+                have_b.extend(std::iter::from_fn(|| b.recv()));
+                clock.advance(Duration::from_millis(50));
+            }
+            assert_eq!(have_a, want_a, "seed {seed}");
+            assert_eq!(have_b, want_b, "seed {seed}");
+
+            let stats = b.stats();
+            assert!(stats.duplicates > 0 && stats.corrupted > 0);
+            assert!(a.stats().retransmitted > 0);
+        }
+    }
+
+    #[test]
+    fn test_arq_retries_exhausted() {
+        let clock = ManualClock::new();
+        let config = ArqConfig {
+            max_retries: 2,
+            ..Default::default()
+        };
+        let mut link = Link::new(config, clock.clone());
+        link.send(b"hello").unwrap();
+
+        assert!(link.poll_transmit().unwrap().is_some());
+        assert_eq!(link.next_timeout(), Some(config.timeout));
+        for _ in 0..2 {
+            assert_eq!(link.poll_transmit().unwrap(), None);
+            clock.advance(config.timeout);
+            assert!(link.poll_transmit().unwrap().is_some());
+        }
+        clock.advance(config.timeout);
+        assert_eq!(
+            link.poll_transmit(),
+            Err(ArqError::RetriesExhausted { seq: 0, retries: 2 })
+        );
+        assert_eq!(
+            link.send(&[0; 1025]),
+            Err(ArqError::TooLarge {
+                size: 1025,
+                max: 1024
+            })
+        );
+    }
+}
@@ -0,0 +1,517 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Relaying of frames between stdio, TCP sockets and pseudo-terminals, with
+//! optional fault injection.
+
+use crate::format::{Format, render};
+use darkbio_cobs::io::{FrameReader, FrameWriter};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(target_os = "linux")]
+use std::{fs::File, path::PathBuf};
+
+/// Endpoint of a bridge, as given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    /// Standard input and output ("stdio")
+    Stdio,
+    /// TCP socket accepting a single connection ("listen:ADDR")
+    Listen(String),
+    /// TCP socket connecting out ("connect:ADDR")
+    Connect(String),
+    /// Freshly allocated pseudo-terminal, Linux only ("pty")
+    Pty,
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "stdio" || s == "-" => Ok(Endpoint::Stdio),
+            None if s == "pty" => Ok(Endpoint::Pty),
+            Some(("listen", addr)) => Ok(Endpoint::Listen(addr.to_string())),
+            Some(("connect", addr)) => Ok(Endpoint::Connect(addr.to_string())),
+            _ => Err(format!(
+                "unknown endpoint {s:?}, want stdio, pty, listen:ADDR or connect:ADDR"
+            )),
+        }
+    }
+}
+
+/// Opened endpoint, split into its two directions.
+pub struct Stream {
+    pub reader: Box<dyn Read + Send>,
+    pub writer: Box<dyn Write + Send>,
+    pub guard: Option<Box<dyn Send>>, // Resource kept alive for the bridge lifetime
+    pub shutdown: Option<Box<dyn FnOnce() + Send>>, // Unblocks the reader once the bridge ends
+}
+
+impl Endpoint {
+    /// Opens the endpoint, blocking until a peer connects for listeners. The
+    /// whereabouts of the endpoint are reported through the log.
+    pub fn open(&self, log: &Log) -> io::Result<Stream> {
+        match self {
+            Endpoint::Stdio => Ok(Stream {
+                reader: Box::new(io::stdin()),
+                writer: Box::new(io::stdout()),
+                guard: None,
+                shutdown: None,
+            }),
+            Endpoint::Listen(addr) => accept(&TcpListener::bind(addr)?, log),
+            Endpoint::Connect(addr) => tcp_stream(TcpStream::connect(addr)?),
+            Endpoint::Pty => open_pty(log),
+        }
+    }
+}
+
+/// Waits for a single connection on a bound listener and opens it.
+pub fn accept(listener: &TcpListener, log: &Log) -> io::Result<Stream> {
+    log.line(format_args!("listening on {}", listener.local_addr()?));
+    let (conn, peer) = listener.accept()?;
+    log.line(format_args!("accepted {peer}"));
+    tcp_stream(conn)
+}
+
+// Splits a TCP connection into its two directions
+fn tcp_stream(conn: TcpStream) -> io::Result<Stream> {
+    conn.set_nodelay(true)?;
+    let control = conn.try_clone()?;
+    Ok(Stream {
+        reader: Box::new(conn.try_clone()?),
+        writer: Box::new(conn),
+        guard: None,
+        shutdown: Some(Box::new(move || {
+            let _ = control.shutdown(Shutdown::Both);
+        })),
+    })
+}
+
+// Allocates a pseudo-terminal in raw mode, keeping its peer side open so that
+// programs can come and go without hanging up the bridge
+#[cfg(target_os = "linux")]
+fn open_pty(log: &Log) -> io::Result<Stream> {
+    let (master, path) = pty::open()?;
+    log.line(format_args!("pty at {}", path.display()));
+    Ok(Stream {
+        reader: Box::new(master.try_clone()?),
+        writer: Box::new(master),
+        guard: Some(Box::new(pty::open_peer(&path)?)),
+        shutdown: None,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_pty(_: &Log) -> io::Result<Stream> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "ptys are only supported on Linux",
+    ))
+}
+
+/// Minimal pseudo-terminal handling on top of libc.
+#[cfg(target_os = "linux")]
+pub mod pty {
+    use super::*;
+    use std::ffi::CStr;
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::os::unix::fs::OpenOptionsExt;
+
+    /// Allocates a pseudo-terminal, returning its controlling side and the path
+    /// of the terminal side.
+    pub fn open() -> io::Result<(File, PathBuf)> {
+        // SAFETY: the fd is checked and immediately owned by a File, and the
+        // name buffer outlives the calls writing into and reading from it
+        unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let master = File::from_raw_fd(fd);
+            if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut name = [0 as libc::c_char; 128];
+            let err = libc::ptsname_r(fd, name.as_mut_ptr(), name.len());
+            if err != 0 {
+                return Err(io::Error::from_raw_os_error(err));
+            }
+            let path = CStr::from_ptr(name.as_ptr()).to_string_lossy().into_owned();
+            Ok((master, PathBuf::from(path)))
+        }
+    }
+
+    /// Opens the terminal side of a pseudo-terminal and switches it into raw
+    /// mode, so that frames pass through the line discipline untouched.
+    pub fn open_peer(path: &PathBuf) -> io::Result<File> {
+        let peer = File::options()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+
+        // SAFETY: termios is plain data, initialized by tcgetattr before use
+        unsafe {
+            let mut tio: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(peer.as_raw_fd(), &mut tio) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            libc::cfmakeraw(&mut tio);
+            if libc::tcsetattr(peer.as_raw_fd(), libc::TCSANOW, &tio) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(peer)
+    }
+}
+
+/// Faults to inject into the relayed frames, as probabilities per frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Faults {
+    pub drop: f64,
+    pub corrupt: f64,
+    pub delay: f64,
+    pub delay_by: Duration,
+}
+
+/// Shared sink of the bridge log lines.
+#[derive(Clone)]
+pub struct Log {
+    out: Option<Arc<Mutex<Box<dyn Write + Send>>>>,
+}
+
+impl Log {
+    /// Creates a log writing into a stream, or discarding everything.
+    pub fn new(out: Option<Box<dyn Write + Send>>) -> Self {
+        Self {
+            out: out.map(|out| Arc::new(Mutex::new(out))),
+        }
+    }
+
+    // Writes a line into the log, ignoring failures
+    fn line(&self, args: fmt::Arguments<'_>) {
+        if let Some(out) = &self.out {
+            let mut out = out.lock().unwrap();
+            let _ = writeln!(out, "{args}");
+            let _ = out.flush();
+        }
+    }
+}
+
+/// Counters of a relay direction.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RelayStats {
+    pub relayed: usize,
+    pub malformed: usize,
+    pub dropped: usize,
+    pub corrupted: usize,
+    pub delayed: usize,
+}
+
+/// Xorshift generator deciding the faults, reproducible from its seed.
+pub struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator from a seed, or from the clock if none is given.
+    pub fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64)
+        });
+        Self(seed | 1)
+    }
+
+    // Returns the next pseudo-random number
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // Returns true with the given probability
+    fn chance(&mut self, p: f64) -> bool {
+        p > 0.0 && ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+}
+
+/// Relays frames from a reader into a writer until the reader ends, logging
+/// each one and injecting the requested faults. Malformed frames are logged and
+/// skipped. Corruption flips a random bit of the payload, so the frame stays
+/// well formed and it's up to the peer's integrity checks to catch it.
+pub fn relay<R: Read, W: Write>(
+    label: &str,
+    reader: R,
+    writer: W,
+    faults: Faults,
+    rng: &mut Rng,
+    log: &Log,
+) -> io::Result<RelayStats> {
+    let mut reader = FrameReader::new(reader);
+    let mut writer = FrameWriter::new(writer);
+    let mut stats = RelayStats::default();
+
+    for index in 0.. {
+        let mut frame = match reader.read_frame() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                stats.malformed += 1;
+                log.line(format_args!("{label} #{index} malformed: {err}"));
+                continue;
+            }
+            Err(err) if is_hangup(&err) => break,
+            Err(err) => return Err(err),
+        };
+        let dropped = rng.chance(faults.drop);
+        let corrupted = !dropped && !frame.is_empty() && rng.chance(faults.corrupt);
+        let delayed = !dropped && !corrupted && rng.chance(faults.delay);
+        if corrupted {
+            let bit = rng.next() as usize % (frame.len() * 8);
+            frame[bit / 8] ^= 1 << (bit % 8);
+        }
+        let fault = [
+            (dropped, " dropped"),
+            (corrupted, " corrupted"),
+            (delayed, " delayed"),
+        ]
+        .into_iter()
+        .find_map(|(hit, name)| hit.then_some(name))
+        .unwrap_or_default();
+        log.line(format_args!(
+            "{label} #{index} {} bytes{fault}: {} |{}|",
+            frame.len(),
+            String::from_utf8_lossy(&render(Format::Hex, &frame)),
+            text(&frame)
+        ));
+        stats.corrupted += corrupted as usize;
+        stats.delayed += delayed as usize;
+        if dropped {
+            stats.dropped += 1;
+            continue;
+        }
+        if delayed {
+            thread::sleep(faults.delay_by);
+        }
+        writer.write_frame(&frame)?;
+        writer.flush()?;
+        stats.relayed += 1;
+    }
+    Ok(stats)
+}
+
+// Reports whether a read failed because the other side of the stream went away,
+// which is how ptys report their end
+fn is_hangup(err: &io::Error) -> bool {
+    err.raw_os_error() == Some(5) // EIO
+        || matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionReset | io::ErrorKind::UnexpectedEof
+        )
+}
+
+/// Opens two endpoints and relays frames between them in both directions until
+/// either side ends.
+pub fn bridge(
+    a: &Endpoint,
+    b: &Endpoint,
+    faults: Faults,
+    seed: Option<u64>,
+    log: &Log,
+) -> io::Result<()> {
+    let a = a.open(log)?;
+    let b = b.open(log)?;
+    bridge_streams(a, b, faults, seed, log)
+}
+
+/// Relays frames between two opened streams in both directions until either
+/// side ends, then shuts both down so the other direction's relay is not left
+/// blocked on a read. Stdio and ptys cannot be shut down, their relays linger
+/// until the process exits.
+pub fn bridge_streams(
+    a: Stream,
+    b: Stream,
+    faults: Faults,
+    seed: Option<u64>,
+    log: &Log,
+) -> io::Result<()> {
+    let mut seeds = Rng::new(seed);
+    let (done, wait) = mpsc::channel();
+    for (label, reader, writer) in [("a>b", a.reader, b.writer), ("b>a", b.reader, a.writer)] {
+        let mut rng = Rng::new(Some(seeds.next()));
+        let (done, log) = (done.clone(), log.clone());
+        thread::spawn(move || {
+            let result = relay(label, reader, writer, faults, &mut rng, &log);
+            if let Ok(stats) = &result {
+                log.line(format_args!("{label} ended: {stats:?}"));
+            }
+            let _ = done.send(result);
+        });
+    }
+    let _guards = (a.guard, b.guard);
+    let result = wait.recv().expect("relay threads hold the sender");
+    for shutdown in [a.shutdown, b.shutdown].into_iter().flatten() {
+        shutdown();
+    }
+    result.map(|_| ())
+}
+
+// Formats bytes as printable ASCII, dotting out the rest
+fn text(data: &[u8]) -> String {
+    data.iter()
+        .map(|&b| match b {
+            0x20..=0x7e => b as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Log collecting the lines into a shared buffer
+    #[derive(Clone, Default)]
+    struct Lines(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Lines {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Reads all the frames available from a reader
+    fn frames(data: &[u8]) -> Vec<Vec<u8>> {
+        FrameReader::new(data).map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn test_relay_faults() {
+        let input = b"\x02a\x00\x03bc\x00\x03d\x00\x02e\x00";
+        let lines = Lines::default();
+        let log = Log::new(Some(Box::new(lines.clone())));
+
+        let mut out = Vec::new();
+        let stats = relay(
+            "t",
+            &input[..],
+            &mut out,
+            Faults::default(),
+            &mut Rng::new(Some(1)),
+            &log,
+        )
+        .unwrap();
+        assert_eq!((stats.relayed, stats.malformed), (3, 1));
+        assert_eq!(frames(&out), [b"a".to_vec(), b"bc".to_vec(), b"e".to_vec()]);
+
+        let log_text = String::from_utf8(lines.0.lock().unwrap().clone()).unwrap();
+        assert!(log_text.contains("t #1 2 bytes: 6263 |bc|"));
+        assert!(log_text.contains("t #2 malformed"));
+
+        let all = Faults {
+            drop: 1.0,
+            ..Faults::default()
+        };
+        let mut out = Vec::new();
+        let stats = relay(
+            "t",
+            &input[..],
+            &mut out,
+            all,
+            &mut Rng::new(Some(1)),
+            &Log::new(None),
+        )
+        .unwrap();
+        assert_eq!((stats.relayed, stats.dropped), (0, 3));
+        assert!(out.is_empty());
+
+        let all = Faults {
+            corrupt: 1.0,
+            ..Faults::default()
+        };
+        let mut out = Vec::new();
+        relay(
+            "t",
+            &input[..],
+            &mut out,
+            all,
+            &mut Rng::new(Some(1)),
+            &Log::new(None),
+        )
+        .unwrap();
+        let out = frames(&out);
+        assert_eq!(out.len(), 3);
+        assert!(out[1] != b"bc" && out[1].len() == 2);
+    }
+
+    #[test]
+    fn test_bridge_tcp() {
+        // Bridge a listener and a connecting socket, with a server at the far end
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let far = server.local_addr().unwrap();
+
+        let near = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(near.local_addr().unwrap()).unwrap();
+
+        let b: Endpoint = format!("connect:{far}").parse().unwrap();
+        let bridge = thread::spawn(move || {
+            let log = Log::new(None);
+            let a = accept(&near, &log)?;
+            let b = b.open(&log)?;
+            bridge_streams(a, b, Faults::default(), Some(1), &log)
+        });
+        let (peer, _) = server.accept().unwrap();
+
+        FrameWriter::new(&client).write_frame(b"ping").unwrap();
+        let mut reader = FrameReader::new(&peer);
+        assert_eq!(reader.read_frame().unwrap().unwrap(), b"ping");
+
+        FrameWriter::new(&peer).write_frame(b"pong").unwrap();
+        assert_eq!(
+            FrameReader::new(&client).read_frame().unwrap().unwrap(),
+            b"pong"
+        );
+
+        // Hanging up one side must also hang up the other one
+        drop(client);
+        bridge.join().unwrap().unwrap();
+        assert!(reader.read_frame().unwrap().is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_relay_pty() {
+        let (master, path) = pty::open().unwrap();
+        let mut peer = pty::open_peer(&path).unwrap();
+
+        // Raw bytes including newlines and control characters must pass intact
+        let payload = b"\r\n\x03\x04\x11\x13\x7f";
+        FrameWriter::new(&mut peer).write_frame(payload).unwrap();
+        FrameWriter::new(&mut peer).write_frame(b"end").unwrap();
+
+        let mut reader = FrameReader::new(&master);
+        assert_eq!(reader.read_frame().unwrap().unwrap(), payload);
+        assert_eq!(reader.read_frame().unwrap().unwrap(), b"end");
+
+        let mut writer = FrameWriter::new(&master);
+        writer.write_frame(payload).unwrap();
+        assert_eq!(
+            FrameReader::new(&peer).read_frame().unwrap().unwrap(),
+            payload
+        );
+
+        assert!("pty".parse::<Endpoint>().is_ok());
+        assert!("serial:/dev/ttyUSB0".parse::<Endpoint>().is_err());
+    }
+}
@@ -0,0 +1,137 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Annotated dump of the structure of encoded frames.
+
+use crate::format::{Format, render};
+use darkbio_cobs::Explanation;
+use std::io::{self, Write};
+
+/// Number of bytes printed per line of a dump.
+const WIDTH: usize = 16;
+
+/// Width of the data column, in characters.
+const DATA_COLUMN: usize = WIDTH * 3 - 1;
+
+/// Writes the runs of an encoded frame, the position of its error (if any) and
+/// the decoded payload.
+pub fn write_explanation<W: Write>(
+    out: &mut W,
+    encoded: &[u8],
+    exp: &Explanation,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "{} bytes encoded in {} runs, {} bytes decoded",
+        encoded.len(),
+        exp.runs.len(),
+        exp.decoded.len()
+    )?;
+    if !exp.runs.is_empty() {
+        writeln!(
+            out,
+            "  offset  code  {:<DATA_COLUMN$}  decoded  end",
+            "data"
+        )?;
+    }
+    for run in &exp.runs {
+        // Mark how the run terminates: with an implied zero, at a 254 byte block
+        // boundary, at the end of the frame or with an error
+        let end = if run.broken {
+            "broken"
+        } else if run.implies_zero {
+            "zero"
+        } else if run.full_block {
+            "block"
+        } else {
+            "end"
+        };
+        let data = &encoded[run.data.clone()];
+        let mut lines = data.chunks(WIDTH);
+
+        let first = hex(lines.next().unwrap_or_default());
+        writeln!(
+            out,
+            "  {:>6}  {:02x}    {first:<DATA_COLUMN$}  {:>7}  {end}",
+            run.offset, run.code, run.decoded
+        )?;
+        for line in lines {
+            writeln!(out, "{:16}{}", "", hex(line))?;
+        }
+    }
+    if let Some(err) = &exp.error {
+        writeln!(out, "error: {err}")?;
+        if let Some(at) = err.position() {
+            let row = at / WIDTH * WIDTH;
+            let end = (row + WIDTH).min(encoded.len());
+            writeln!(out, "  {row:>6}  {}", hex(&encoded[row..end]))?;
+            writeln!(out, "  {:6}  {:pad$}^^", "", "", pad = (at - row) * 3)?;
+        }
+    }
+    writeln!(out, "decoded:")?;
+    for (i, line) in exp.decoded.chunks(WIDTH).enumerate() {
+        let text: String = line
+            .iter()
+            .map(|&b| match b {
+                0x20..=0x7e => b as char,
+                _ => '.',
+            })
+            .collect();
+        writeln!(
+            out,
+            "  {:>6}  {:<DATA_COLUMN$}  |{text}|",
+            i * WIDTH,
+            hex(line)
+        )?;
+    }
+    Ok(())
+}
+
+// Formats bytes as space separated hex pairs
+fn hex(data: &[u8]) -> String {
+    let digits = render(Format::Hex, data);
+    let pairs: Vec<_> = digits.chunks(2).map(String::from_utf8_lossy).collect();
+    pairs.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use darkbio_cobs::explain;
+
+    // Renders the explanation of a frame into a string
+    fn render(encoded: &[u8]) -> String {
+        let mut out = Vec::new();
+        write_explanation(&mut out, encoded, &explain(encoded)).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_explain_valid() {
+        let mut encoded = vec![0x03, b'h', b'i', 0xff];
+        encoded.extend(std::iter::repeat_n(b'x', 254));
+        encoded.extend([0x02, b'!']);
+
+        let out = render(&encoded);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "260 bytes encoded in 3 runs, 258 bytes decoded");
+        assert!(lines[2].starts_with("       0  03    68 69 ") && lines[2].ends_with("0  zero"));
+        assert!(lines[3].starts_with("       3  ff    78 78 ") && lines[3].ends_with("3  block"));
+        assert!(lines[19].starts_with("     258  02    21 ") && lines[19].ends_with("257  end"));
+        assert_eq!(lines[20], "decoded:");
+        assert!(
+            lines[21].starts_with("       0  68 69 00 78")
+                && lines[21].ends_with("|hi.xxxxxxxxxxxxx|")
+        );
+    }
+
+    #[test]
+    fn test_explain_broken() {
+        let out = render(&[0x02, 0x11, 0x04, 0x22, 0x00, 0x33]);
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[3].starts_with("       2  04    22 ") && lines[3].ends_with("2  broken"));
+        assert_eq!(lines[4], "error: zero byte in data at position 4");
+        assert_eq!(lines[5], "       0  02 11 04 22 00 33");
+        assert_eq!(lines[6], "                      ^^");
+    }
+}
@@ -0,0 +1,278 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Textual representations of the bytes read and written by the tool, and the
+//! splitting of inputs into records (payloads or frames).
+
+use crate::Failure;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// Representation of binary data on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Bytes as they are
+    Raw,
+    /// Hexadecimal digits, whitespace ignored
+    Hex,
+    /// Standard base64 with padding, whitespace ignored
+    Base64,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Raw => "raw",
+            Format::Hex => "hex",
+            Format::Base64 => "base64",
+        })
+    }
+}
+
+/// Side of the COBS transform the records belong to, which determines how they
+/// are separated from each other in multi-record mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// Payloads, separated by newlines
+    Decoded,
+    /// Frames, separated by 0x00 delimiters
+    Encoded,
+}
+
+/// Input record along with its position in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub offset: usize, // Byte offset in the input (line start for text formats)
+    pub data: Vec<u8>,
+}
+
+/// Error returned if a textual input cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid {format} input at offset {offset}: {reason}")]
+pub struct FormatError {
+    pub format: Format,
+    pub offset: usize,
+    pub reason: String,
+}
+
+/// Parses the textual representation of some bytes.
+pub fn parse(format: Format, text: &[u8], offset: usize) -> Result<Vec<u8>, FormatError> {
+    let fail = |reason: String| FormatError {
+        format,
+        offset,
+        reason,
+    };
+    let compact: Vec<u8> = text
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    match format {
+        Format::Raw => Ok(text.to_vec()),
+        Format::Hex => {
+            if !compact.len().is_multiple_of(2) {
+                return Err(fail("odd number of digits".into()));
+            }
+            compact
+                .chunks(2)
+                .map(|pair| {
+                    std::str::from_utf8(pair)
+                        .ok()
+                        .and_then(|s| u8::from_str_radix(s, 16).ok())
+                        .ok_or_else(|| {
+                            fail(format!("bad digits {:?}", String::from_utf8_lossy(pair)))
+                        })
+                })
+                .collect()
+        }
+        Format::Base64 => STANDARD
+            .decode(&compact)
+            .map_err(|err| fail(err.to_string())),
+    }
+}
+
+/// Renders some bytes in a textual representation.
+pub fn render(format: Format, data: &[u8]) -> Vec<u8> {
+    match format {
+        Format::Raw => data.to_vec(),
+        Format::Hex => data
+            .iter()
+            .flat_map(|b| format!("{b:02x}").into_bytes())
+            .collect(),
+        Format::Base64 => STANDARD.encode(data).into_bytes(),
+    }
+}
+
+/// Iterator splitting an input into records and parsing them. In single-record
+/// mode, the whole input is one record (with a trailing 0x00 delimiter tolerated
+/// on raw frames). In multi-record mode, text formats have one record per line,
+/// while raw payloads are newline separated and raw frames 0x00 delimited.
+///
+/// Records are yielded as soon as they are complete, so streams can be processed
+/// live.
+pub(crate) struct Records<R: BufRead> {
+    input: R,
+    format: Format,
+    side: Side,
+    multi: bool,
+    offset: usize, // Position of the next record in the input
+    done: bool,    // Whether the input was exhausted or failed
+}
+
+impl<R: BufRead> Records<R> {
+    /// Creates a record splitter on top of an input stream.
+    pub fn new(input: R, format: Format, side: Side, multi: bool) -> Self {
+        Self {
+            input,
+            format,
+            side,
+            multi,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    // Reads the next raw piece of input (without the separator), or `None` if
+    // the input was exhausted
+    fn read_piece(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut piece = Vec::new();
+        if !self.multi {
+            self.input.read_to_end(&mut piece)?;
+            if self.format == Format::Raw && self.side == Side::Encoded && piece.last() == Some(&0)
+            {
+                piece.pop();
+            }
+            return Ok(Some(piece));
+        }
+        let separator = match (self.format, self.side) {
+            (Format::Raw, Side::Encoded) => 0x00,
+            _ => b'\n',
+        };
+        let n = self.input.read_until(separator, &mut piece)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        self.offset += n;
+        if piece.last() == Some(&separator) {
+            piece.pop();
+        }
+        if separator == b'\n' && piece.last() == Some(&b'\r') {
+            piece.pop();
+        }
+        Ok(Some(piece))
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = Result<Record, Failure>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let start = self.offset;
+            let piece = match self.read_piece() {
+                Ok(Some(piece)) => piece,
+                Ok(None) => break,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err.into()));
+                }
+            };
+            self.done |= !self.multi;
+
+            // Frames can't be empty, so skip blank ones between delimiters
+            let blank = match self.format {
+                Format::Raw => piece.is_empty(),
+                _ => piece.iter().all(|b| b.is_ascii_whitespace()),
+            };
+            if self.multi && blank && self.side == Side::Encoded {
+                continue;
+            }
+            let record = parse(self.format, &piece, start).map(|data| Record {
+                offset: start,
+                data,
+            });
+            self.done |= record.is_err();
+            return Some(record.map_err(Into::into));
+        }
+        self.done = true;
+        None
+    }
+}
+
+/// Writes a record out, followed by the separator appropriate for the mode.
+pub fn write_record<W: Write>(
+    out: &mut W,
+    data: &[u8],
+    format: Format,
+    side: Side,
+    multi: bool,
+) -> io::Result<()> {
+    out.write_all(&render(format, data))?;
+    match (format, side) {
+        (Format::Raw, _) if !multi => Ok(()),
+        (Format::Raw, Side::Encoded) => out.write_all(&[0]),
+        _ => out.write_all(b"\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_render() {
+        let data = [0x00, 0x11, 0xab, 0xff];
+        for format in [Format::Raw, Format::Hex, Format::Base64] {
+            assert_eq!(parse(format, &render(format, &data), 0).unwrap(), data);
+        }
+        assert_eq!(parse(Format::Hex, b"00 11\nAB ff", 0).unwrap(), data);
+        assert_eq!(parse(Format::Base64, b"ABGr\n/w==", 0).unwrap(), data);
+
+        assert!(parse(Format::Hex, b"001", 0).is_err());
+        assert!(parse(Format::Hex, b"0g", 0).is_err());
+        assert!(parse(Format::Base64, b"A", 0).is_err());
+    }
+
+    // Splits an input into records, panicking on failure
+    fn records(input: &[u8], format: Format, side: Side, multi: bool) -> Vec<(usize, Vec<u8>)> {
+        Records::new(input, format, side, multi)
+            .map(|r| r.map(|r| (r.offset, r.data)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_records() {
+        assert_eq!(
+            records(
+                b"\x02\x01\x00\x00\x01\x00\x03",
+                Format::Raw,
+                Side::Encoded,
+                true
+            ),
+            [(0, vec![0x02, 0x01]), (4, vec![0x01]), (6, vec![0x03])]
+        );
+        assert_eq!(
+            records(b"ab\r\n\nc\n", Format::Raw, Side::Decoded, true),
+            [(0, b"ab".to_vec()), (4, vec![]), (5, b"c".to_vec())]
+        );
+        assert_eq!(
+            records(b"0102\n\n03", Format::Hex, Side::Encoded, true),
+            [(0, vec![1, 2]), (6, vec![3])]
+        );
+        assert_eq!(
+            records(b"\x02\x01\x00", Format::Raw, Side::Encoded, false),
+            [(0, vec![0x02, 0x01])]
+        );
+        assert_eq!(
+            records(b"", Format::Raw, Side::Decoded, false),
+            [(0, vec![])]
+        );
+
+        let mut bad = Records::new(&b"01\nzz\n02\n"[..], Format::Hex, Side::Decoded, true);
+        assert!(bad.next().unwrap().is_ok());
+        assert!(matches!(bad.next(), Some(Err(Failure::Format(_)))));
+        assert!(bad.next().is_none());
+    }
+}
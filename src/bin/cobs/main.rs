@@ -0,0 +1,596 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Command line tool for COBS encoding and decoding files and streams.
+
+mod bridge;
+mod explain;
+mod format;
+mod split;
+
+use bridge::{Endpoint, Faults, Log};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use darkbio_cobs::logging::{Entries, LogError};
+use darkbio_cobs::pcap::{self, PcapWriter};
+use darkbio_cobs::{DecodeError, decode, decode_buffer, encode, encode_buffer, explain};
+use format::{Format, FormatError, Records, Side, write_record};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+/// Exit codes of the failures, documented in the help text.
+const EXIT_HELP: &str = "\
+Exit codes:
+  0   success
+  1   I/O error
+  2   invalid command line
+  3   malformed hex or base64 input
+  10  empty frame
+  11  frame too large
+  12  zero code byte
+  13  zero data byte
+  14  chunk overflowing the frame
+  15  malformed log record
+
+With --error-format json, failures are instead reported on stderr as single
+JSON objects carrying their kind, the record index and offset, and the position
+of the fault within the frame.";
+
+#[derive(Parser)]
+#[command(name = "cobs", version, about = "COBS encoder and decoder", after_help = EXIT_HELP)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Format of the failure reports written to stderr
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+}
+
+/// Representation of the failure reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ErrorFormat {
+    /// Human readable message
+    Text,
+    /// One JSON object per failure
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Encodes payloads into COBS frames
+    Encode(Transform),
+    /// Decodes COBS frames into payloads
+    Decode(Transform),
+    /// Dumps the runs of COBS frames, pointing out where they break
+    Explain(Inspect),
+    /// Splits a capture into frames, cataloguing them as JSON Lines
+    Split(Capture),
+    /// Relays frames between two endpoints: stdio, pty, listen:ADDR or
+    /// connect:ADDR
+    Bridge(Relay),
+    /// Converts a stream of COBS frames into a pcapng capture
+    Pcap(Export),
+    /// Pretty-prints a stream of binary log records
+    Logs(Streams),
+}
+
+/// Options of the encoding and decoding commands.
+#[derive(Args)]
+struct Transform {
+    #[command(flatten)]
+    io: Streams,
+
+    /// Format of the input
+    #[arg(short = 'I', long, value_enum, default_value_t = Format::Raw)]
+    input_format: Format,
+
+    /// Format of the output
+    #[arg(short = 'O', long, value_enum, default_value_t = Format::Raw)]
+    output_format: Format,
+
+    /// Process multiple records: 0x00 delimited raw frames, newline separated
+    /// raw payloads, or one hex/base64 record per line
+    #[arg(short, long)]
+    multi: bool,
+
+    /// Keep decoding after a malformed frame, still failing at the end
+    #[arg(short, long, requires = "multi")]
+    keep_going: bool,
+}
+
+/// Options of the frame inspection commands.
+#[derive(Args)]
+struct Inspect {
+    #[command(flatten)]
+    io: Streams,
+
+    /// Format of the input
+    #[arg(short = 'I', long, value_enum, default_value_t = Format::Raw)]
+    input_format: Format,
+
+    /// Process multiple frames: 0x00 delimited raw frames, or one hex/base64
+    /// frame per line
+    #[arg(short, long)]
+    multi: bool,
+}
+
+/// Options of the capture splitting command.
+#[derive(Args)]
+struct Capture {
+    #[command(flatten)]
+    io: Streams,
+
+    /// Format of the capture
+    #[arg(short = 'I', long, value_enum, default_value_t = Format::Raw)]
+    input_format: Format,
+
+    /// Only print the summary statistics, not the individual frames
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+/// Options of the frame relaying command.
+#[derive(Args)]
+struct Relay {
+    /// First endpoint
+    a: Endpoint,
+
+    /// Second endpoint
+    b: Endpoint,
+
+    /// Probability of dropping a frame
+    #[arg(long, default_value_t = 0.0, value_parser = probability)]
+    drop: f64,
+
+    /// Probability of flipping a bit in a frame
+    #[arg(long, default_value_t = 0.0, value_parser = probability)]
+    corrupt: f64,
+
+    /// Probability of delaying a frame
+    #[arg(long, default_value_t = 0.0, value_parser = probability)]
+    delay: f64,
+
+    /// Time to hold back delayed frames for, in milliseconds
+    #[arg(long, default_value_t = 100)]
+    delay_ms: u64,
+
+    /// Seed of the fault injection, for reproducible runs
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Don't log the relayed frames to stderr
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+// Parses a probability argument
+fn probability(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+        _ => Err(format!("{s:?} is not a probability between 0 and 1")),
+    }
+}
+
+/// Options of the capture export command.
+#[derive(Args)]
+struct Export {
+    #[command(flatten)]
+    io: Streams,
+
+    /// Format of the input: 0x00 delimited raw frames, or one hex/base64 frame
+    /// per line
+    #[arg(short = 'I', long, value_enum, default_value_t = Format::Raw)]
+    input_format: Format,
+
+    /// Link-layer type of the packets (147 is DLT_USER0)
+    #[arg(short, long, default_value_t = pcap::LINKTYPE_USER0)]
+    linktype: u16,
+
+    /// Stamp packets with the time their frame was read, instead of zero
+    #[arg(short, long)]
+    timestamps: bool,
+}
+
+/// Input and output selection shared by the commands.
+#[derive(Args)]
+struct Streams {
+    /// File to read, or stdin if omitted or "-"
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+
+    /// File to write, or stdout if omitted or "-"
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+impl Streams {
+    // Opens the selected input stream
+    fn open_input(&self) -> io::Result<Box<dyn BufRead>> {
+        match &self.input {
+            Some(path) if path.as_os_str() != "-" => {
+                Ok(Box::new(BufReader::new(File::open(path)?)))
+            }
+            _ => Ok(Box::new(io::stdin().lock())),
+        }
+    }
+
+    // Opens (or creates) the selected output stream
+    fn open_output(&self) -> io::Result<Box<dyn Write>> {
+        match &self.output {
+            Some(path) if path.as_os_str() != "-" => {
+                Ok(Box::new(BufWriter::new(File::create(path)?)))
+            }
+            _ => Ok(Box::new(BufWriter::new(io::stdout().lock()))),
+        }
+    }
+}
+
+/// Reasons the tool can fail, each mapping to a distinct exit code.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Failure {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Format(#[from] FormatError),
+    #[error("record {index} at offset {offset}: {err}")]
+    Decode {
+        index: usize,
+        offset: usize,
+        err: DecodeError,
+    },
+    #[error("record {index}: {err}")]
+    Log { index: usize, err: LogError },
+}
+
+impl Failure {
+    /// Returns the exit code reporting the failure.
+    fn exit_code(&self) -> u8 {
+        match self {
+            Failure::Io(_) => 1,
+            Failure::Format(_) => 3,
+            Failure::Decode { err, .. }
+            | Failure::Log {
+                err: LogError::Decode(err),
+                ..
+            } => match err {
+                DecodeError::EmptyInput => 10,
+                DecodeError::BufferTooSmall { .. } => 11,
+                DecodeError::ZeroMarker { .. } => 12,
+                DecodeError::ZeroBinary { .. } => 13,
+                DecodeError::ChunkOverflow { .. } => 14,
+            },
+            Failure::Log {
+                err: LogError::Io(_),
+                ..
+            } => 1,
+            Failure::Log { .. } => 15,
+        }
+    }
+
+    /// Formats the failure as a JSON object, for tools driving the command.
+    fn to_json(&self) -> String {
+        let code = self.exit_code();
+        let message = split::quote(&self.to_string());
+        match self {
+            Failure::Io(_) => {
+                format!(r#"{{"error":"Io","exit_code":{code},"message":{message}}}"#)
+            }
+            Failure::Format(err) => format!(
+                r#"{{"error":"Format","exit_code":{code},"offset":{},"message":{message}}}"#,
+                err.offset
+            ),
+            Failure::Decode { index, offset, err } => format!(
+                r#"{{"error":{},"exit_code":{code},"index":{index},"offset":{offset},"position":{},"message":{message}}}"#,
+                split::quote(split::kind(err)),
+                err.position()
+                    .map_or("null".to_string(), |at| at.to_string())
+            ),
+            Failure::Log { index, err } => {
+                let (kind, position) = match err {
+                    LogError::Decode(err) => (split::kind(err), err.position()),
+                    _ => ("Log", None),
+                };
+                format!(
+                    r#"{{"error":{},"exit_code":{code},"index":{index},"position":{},"message":{message}}}"#,
+                    split::quote(kind),
+                    position.map_or("null".to_string(), |at| at.to_string())
+                )
+            }
+        }
+    }
+
+    /// Writes the failure to stderr in the requested format.
+    fn report(&self, format: ErrorFormat) {
+        match format {
+            ErrorFormat::Text => eprintln!("cobs: {self}"),
+            ErrorFormat::Json => eprintln!("{}", self.to_json()),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Command::Encode(opts) => run_transform(opts, Side::Decoded, cli.error_format),
+        Command::Decode(opts) => run_transform(opts, Side::Encoded, cli.error_format),
+        Command::Explain(opts) => run_explain(opts),
+        Command::Split(opts) => run_split(opts),
+        Command::Bridge(opts) => run_bridge(opts),
+        Command::Pcap(opts) => run_pcap(opts),
+        Command::Logs(opts) => run_logs(opts, cli.error_format),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            err.report(cli.error_format);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+// Runs an encoding or decoding command from its input side
+fn run_transform(opts: &Transform, from: Side, errors: ErrorFormat) -> Result<(), Failure> {
+    let input = opts.io.open_input()?;
+    let mut output = opts.io.open_output()?;
+    transform(input, &mut output, opts, from, errors)
+}
+
+// Encodes or decodes all the records of an input into an output
+fn transform<R: BufRead, W: Write>(
+    input: R,
+    output: &mut W,
+    opts: &Transform,
+    from: Side,
+    errors: ErrorFormat,
+) -> Result<(), Failure> {
+    let mut first = None;
+    for (index, record) in Records::new(input, opts.input_format, from, opts.multi).enumerate() {
+        let record = record?;
+        let result = match from {
+            Side::Decoded => {
+                let mut buf = vec![0u8; encode_buffer(record.data.len())];
+                let len = encode(&record.data, &mut buf).expect("buffer sized by encode_buffer");
+                buf.truncate(len);
+                Ok((buf, Side::Encoded))
+            }
+            Side::Encoded => {
+                let mut buf = vec![0u8; decode_buffer(record.data.len())];
+                decode(&record.data, &mut buf).map(|len| {
+                    buf.truncate(len);
+                    (buf, Side::Decoded)
+                })
+            }
+        };
+        match result {
+            Ok((data, side)) => {
+                write_record(output, &data, opts.output_format, side, opts.multi)?;
+                if opts.multi {
+                    output.flush()?;
+                }
+            }
+            Err(err) => {
+                let failure = Failure::Decode {
+                    index,
+                    offset: record.offset,
+                    err,
+                };
+                if !opts.keep_going {
+                    output.flush()?;
+                    return Err(failure);
+                }
+                failure.report(errors);
+                first.get_or_insert(failure);
+            }
+        }
+    }
+    output.flush()?;
+    first.map_or(Ok(()), Err)
+}
+
+// Runs the frame explanation command
+fn run_explain(opts: &Inspect) -> Result<(), Failure> {
+    let input = opts.io.open_input()?;
+    let mut output = opts.io.open_output()?;
+
+    let mut first = None;
+    for (index, record) in
+        Records::new(input, opts.input_format, Side::Encoded, opts.multi).enumerate()
+    {
+        let record = record?;
+        if opts.multi {
+            writeln!(output, "frame {index} at offset {}:", record.offset)?;
+        }
+        let exp = explain(&record.data);
+        explain::write_explanation(&mut output, &record.data, &exp)?;
+        output.flush()?;
+
+        if let Some(err) = exp.error {
+            first.get_or_insert(Failure::Decode {
+                index,
+                offset: record.offset,
+                err,
+            });
+        }
+    }
+    first.map_or(Ok(()), Err)
+}
+
+// Runs the capture splitting command
+fn run_split(opts: &Capture) -> Result<(), Failure> {
+    let input = opts.io.open_input()?;
+    let mut output = opts.io.open_output()?;
+
+    split::split(input, &mut output, opts.input_format, opts.quiet)?;
+    output.flush()?;
+    Ok(())
+}
+
+// Runs the frame relaying command
+fn run_bridge(opts: &Relay) -> Result<(), Failure> {
+    let faults = Faults {
+        drop: opts.drop,
+        corrupt: opts.corrupt,
+        delay: opts.delay,
+        delay_by: Duration::from_millis(opts.delay_ms),
+    };
+    let log = Log::new((!opts.quiet).then(|| Box::new(io::stderr()) as Box<dyn Write + Send>));
+    bridge::bridge(&opts.a, &opts.b, faults, opts.seed, &log)?;
+    Ok(())
+}
+
+// Runs the capture export command
+fn run_pcap(opts: &Export) -> Result<(), Failure> {
+    let input = opts.io.open_input()?;
+    let mut pcap = PcapWriter::new(opts.io.open_output()?, opts.linktype)?;
+
+    for record in Records::new(input, opts.input_format, Side::Encoded, true) {
+        let record = record?;
+        let timestamp = opts.timestamps.then(SystemTime::now);
+        pcap.write_frame(&record.data, timestamp)?;
+        pcap.flush()?;
+    }
+    Ok(())
+}
+
+// Runs the log printing command
+fn run_logs(opts: &Streams, errors: ErrorFormat) -> Result<(), Failure> {
+    let input = opts.open_input()?;
+    let mut output = opts.open_output()?;
+    logs(input, &mut output, errors)
+}
+
+// Prints all the log records of an input, reporting malformed records and
+// carrying on, failing with the first of them at the end
+fn logs<R: Read, W: Write>(input: R, output: &mut W, errors: ErrorFormat) -> Result<(), Failure> {
+    let mut first = None;
+    for (index, entry) in Entries::new(input).enumerate() {
+        match entry {
+            Ok(entry) => writeln!(output, "{entry}")?,
+            Err(LogError::Io(err)) => {
+                output.flush()?;
+                return Err(err.into());
+            }
+            Err(err) => {
+                let failure = Failure::Log { index, err };
+                failure.report(errors);
+                first.get_or_insert(failure);
+            }
+        }
+        output.flush()?;
+    }
+    first.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs a command line against an in-memory input, returning the output
+    fn run(args: &[&str], input: &[u8]) -> (Vec<u8>, Result<(), Failure>) {
+        let cli = Cli::try_parse_from(std::iter::once("cobs").chain(args.iter().copied())).unwrap();
+        let (opts, from) = match &cli.command {
+            Command::Encode(opts) => (opts, Side::Decoded),
+            Command::Decode(opts) => (opts, Side::Encoded),
+            _ => unreachable!("not a transform command"),
+        };
+        let mut output = Vec::new();
+        let result = transform(input, &mut output, opts, from, cli.error_format);
+        (output, result)
+    }
+
+    #[test]
+    fn test_single_record() {
+        let (out, res) = run(&["encode"], b"\x11\x00\x22");
+        assert!(res.is_ok());
+        assert_eq!(out, b"\x02\x11\x02\x22");
+
+        let (out, res) = run(&["decode", "-O", "hex"], b"\x02\x11\x02\x22\x00");
+        assert!(res.is_ok());
+        assert_eq!(out, b"110022\n");
+
+        let (out, res) = run(&["encode", "-I", "base64", "-O", "base64"], b"EQAi\n");
+        assert!(res.is_ok());
+        assert_eq!(out, b"AhECIg==\n");
+    }
+
+    #[test]
+    fn test_multi_record() {
+        let (out, res) = run(&["encode", "-m", "-I", "hex"], b"11\n\n2200\n");
+        assert!(res.is_ok());
+        assert_eq!(out, b"\x02\x11\x00\x01\x00\x02\x22\x01\x00");
+
+        let (out, res) = run(&["decode", "-m", "-O", "hex"], &out);
+        assert!(res.is_ok());
+        assert_eq!(out, b"11\n\n2200\n");
+    }
+
+    #[test]
+    fn test_decode_failures() {
+        let (out, res) = run(&["decode", "-m"], b"\x02a\x00\x03b\x00\x02c\x00");
+        assert_eq!(out, b"a\n");
+        let err = res.unwrap_err();
+        assert_eq!(err.exit_code(), 14);
+        assert_eq!(
+            err.to_string(),
+            "record 1 at offset 3: chunk overflow at position 0: chunk 3 exceeds data length 2"
+        );
+        let (out, res) = run(
+            &["decode", "-m", "-k", "-I", "hex"],
+            b"0261\n0200\n01\n0263\n",
+        );
+        assert_eq!(out, b"a\n\nc\n");
+        assert_eq!(res.unwrap_err().exit_code(), 13);
+
+        let (_, res) = run(&["decode"], b"");
+        assert_eq!(res.unwrap_err().exit_code(), 10);
+        let (_, res) = run(&["decode", "-I", "hex"], b"0");
+        assert_eq!(res.unwrap_err().exit_code(), 3);
+    }
+
+    #[test]
+    fn test_error_report() {
+        let (_, res) = run(
+            &["decode", "-m", "--error-format", "json"],
+            b"\x02a\x00\x03b\x00",
+        );
+        assert_eq!(
+            res.unwrap_err().to_json(),
+            r#"{"error":"ChunkOverflow","exit_code":14,"index":1,"offset":3,"position":0,"message":"record 1 at offset 3: chunk overflow at position 0: chunk 3 exceeds data length 2"}"#
+        );
+        let (_, res) = run(&["decode"], b"");
+        assert_eq!(
+            res.unwrap_err().to_json(),
+            r#"{"error":"EmptyInput","exit_code":10,"index":0,"offset":0,"position":null,"message":"record 0 at offset 0: empty input"}"#
+        );
+        let (_, res) = run(&["decode", "-I", "hex"], b"0");
+        assert!(
+            res.unwrap_err()
+                .to_json()
+                .starts_with(r#"{"error":"Format","exit_code":3,"offset":0,"#)
+        );
+    }
+
+    #[test]
+    fn test_log_failures() {
+        let mut out = Vec::new();
+        let input = b"\x02\x09\x00\x05\x01\x00";
+        let err = logs(&input[..], &mut out, ErrorFormat::Json).unwrap_err();
+        assert!(out.is_empty());
+        assert_eq!(
+            err.to_json(),
+            r#"{"error":"Log","exit_code":15,"index":0,"position":null,"message":"record 0: unknown level 9"}"#
+        );
+        let err = logs(&input[3..], &mut out, ErrorFormat::Json).unwrap_err();
+        assert_eq!(
+            err.to_json(),
+            r#"{"error":"ChunkOverflow","exit_code":14,"index":0,"position":0,"message":"record 0: chunk overflow at position 0: chunk 5 exceeds data length 2"}"#
+        );
+        // A torn trailing frame aborts as an I/O error instead
+        let err = logs(&input[..4], &mut out, ErrorFormat::Json).unwrap_err();
+        assert!(matches!(err, Failure::Io(_)));
+        assert!(logs(&input[..0], &mut out, ErrorFormat::Json).is_ok());
+    }
+}
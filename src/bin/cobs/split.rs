@@ -0,0 +1,204 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Splitting of raw captures into frames, catalogued as JSON Lines.
+
+use crate::Failure;
+use crate::format::{Format, parse, render};
+use darkbio_cobs::{DecodeError, decode, decode_buffer};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+
+/// Statistics gathered over all the frames of a capture.
+#[derive(Debug, Default)]
+pub struct Stats {
+    frames: usize,
+    valid: usize,
+    encoded: usize,              // Bytes of the valid frames, delimiters included
+    decoded: usize,              // Bytes of the payloads of the valid frames
+    sizes: BTreeMap<u32, usize>, // Frame counts by log2 of the encoded length
+    errors: BTreeMap<&'static str, usize>, // Frame counts by error variant
+}
+
+impl Stats {
+    // Renders the statistics as a JSON object
+    fn to_json(&self) -> String {
+        let overhead = match self.decoded {
+            0 => "null".to_string(),
+            n => format!("{:.6}", (self.encoded - n) as f64 / n as f64),
+        };
+        let sizes: Vec<String> = self
+            .sizes
+            .iter()
+            .map(|(&bits, count)| {
+                let (min, max) = (1usize << bits, (1usize << (bits + 1)) - 1);
+                format!(r#"{{"min":{min},"max":{max},"count":{count}}}"#)
+            })
+            .collect();
+        let errors: Vec<String> = self
+            .errors
+            .iter()
+            .map(|(kind, count)| format!(r#""{kind}":{count}"#))
+            .collect();
+        format!(
+            r#"{{"frames":{},"valid":{},"invalid":{},"encoded_bytes":{},"decoded_bytes":{},"overhead":{overhead},"sizes":[{}],"errors":{{{}}}}}"#,
+            self.frames,
+            self.valid,
+            self.frames - self.valid,
+            self.encoded,
+            self.decoded,
+            sizes.join(","),
+            errors.join(",")
+        )
+    }
+}
+
+/// Splits a capture at its 0x00 delimiters and decodes each frame, writing one
+/// JSON object per frame (unless quiet) and a final one with the statistics.
+/// Empty frames between consecutive delimiters are skipped; a trailing frame
+/// without a delimiter is still decoded, but flagged as such.
+///
+/// Corrupt frames are catalogued, not failed on; only I/O errors and malformed
+/// text inputs abort the split.
+pub(crate) fn split<R: BufRead, W: Write>(
+    mut input: R,
+    output: &mut W,
+    format: Format,
+    quiet: bool,
+) -> Result<Stats, Failure> {
+    let mut text = Vec::new();
+    input.read_to_end(&mut text)?;
+    let capture = parse(format, &text, 0)?;
+
+    let mut stats = Stats::default();
+    let mut offset = 0;
+    for frame in capture.split(|&b| b == 0) {
+        let start = offset;
+        offset += frame.len() + 1;
+        if frame.is_empty() {
+            continue;
+        }
+        let delimited = offset <= capture.len();
+        let index = stats.frames;
+
+        stats.frames += 1;
+        *stats.sizes.entry(frame.len().ilog2()).or_default() += 1;
+
+        let mut buf = vec![0u8; decode_buffer(frame.len())];
+        let (decoded, error) = match decode(frame, &mut buf) {
+            Ok(len) => {
+                stats.valid += 1;
+                stats.encoded += frame.len() + 1;
+                stats.decoded += len;
+                (Some(&buf[..len]), None)
+            }
+            Err(err) => {
+                *stats.errors.entry(kind(&err)).or_default() += 1;
+                (None, Some(err))
+            }
+        };
+        if quiet {
+            continue;
+        }
+        let (decoded_len, payload) = match decoded {
+            Some(data) => (
+                data.len().to_string(),
+                format!(
+                    "\"{}\"",
+                    String::from_utf8_lossy(&render(Format::Hex, data))
+                ),
+            ),
+            None => ("null".to_string(), "null".to_string()),
+        };
+        // Positions are relative to the frame, like in the error messages
+        let error = match error {
+            Some(err) => format!(
+                r#"{{"kind":"{}","position":{},"message":{}}}"#,
+                kind(&err),
+                err.position()
+                    .map_or("null".to_string(), |at| at.to_string()),
+                quote(&err.to_string())
+            ),
+            None => "null".to_string(),
+        };
+        writeln!(
+            output,
+            r#"{{"index":{index},"offset":{start},"encoded_len":{},"delimited":{delimited},"decoded_len":{decoded_len},"payload":{payload},"error":{error}}}"#,
+            frame.len()
+        )?;
+    }
+    writeln!(output, r#"{{"summary":{}}}"#, stats.to_json())?;
+    Ok(stats)
+}
+
+// Returns the name of the variant of a decoding error
+pub fn kind(err: &DecodeError) -> &'static str {
+    match err {
+        DecodeError::EmptyInput => "EmptyInput",
+        DecodeError::BufferTooSmall { .. } => "BufferTooSmall",
+        DecodeError::ZeroMarker { .. } => "ZeroMarker",
+        DecodeError::ZeroBinary { .. } => "ZeroBinary",
+        DecodeError::ChunkOverflow { .. } => "ChunkOverflow",
+    }
+}
+
+// Quotes a string as a JSON string literal
+pub fn quote(text: &str) -> String {
+    let mut out = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c < ' ' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_capture() {
+        let capture = b"\x00\x02\x11\x02\x22\x00\x00\x03\x33\x00\x44\x00\x05\x55";
+        let mut out = Vec::new();
+        let stats = split(&capture[..], &mut out, Format::Raw, false).unwrap();
+        assert_eq!((stats.frames, stats.valid), (4, 1));
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            r#"{"index":0,"offset":1,"encoded_len":4,"delimited":true,"decoded_len":3,"payload":"110022","error":null}"#
+        );
+        assert_eq!(
+            lines[1],
+            r#"{"index":1,"offset":7,"encoded_len":2,"delimited":true,"decoded_len":null,"payload":null,"error":{"kind":"ChunkOverflow","position":0,"message":"chunk overflow at position 0: chunk 3 exceeds data length 2"}}"#
+        );
+        assert!(lines[3].contains(r#""offset":12,"encoded_len":2,"delimited":false"#));
+        assert_eq!(
+            lines[4],
+            r#"{"summary":{"frames":4,"valid":1,"invalid":3,"encoded_bytes":5,"decoded_bytes":3,"overhead":0.666667,"sizes":[{"min":1,"max":1,"count":1},{"min":2,"max":3,"count":2},{"min":4,"max":7,"count":1}],"errors":{"ChunkOverflow":3}}}"#
+        );
+    }
+
+    #[test]
+    fn test_split_quiet_hex() {
+        let mut out = Vec::new();
+        split(&b"021102 22 00\n"[..], &mut out, Format::Hex, true).unwrap();
+        assert!(out.starts_with(br#"{"summary":{"frames":1,"valid":1,"#));
+        assert_eq!(out.iter().filter(|&&b| b == b'\n').count(), 1);
+
+        assert!(matches!(
+            split(&b"0"[..], &mut Vec::new(), Format::Hex, true),
+            Err(Failure::Format(_))
+        ));
+        assert_eq!(quote("a\"b\\\n"), r#""a\"b\\\u000a""#);
+    }
+}
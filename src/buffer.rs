@@ -0,0 +1,224 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+use crate::DecodeError;
+
+/// Fixed capacity accumulator that decodes a stream of 0x00 delimited COBS frames
+/// one byte at a time, writing the decoded payload directly into its internal
+/// buffer of `N` bytes.
+///
+/// The accumulator never allocates and never panics, making it suitable to be
+/// fed straight from a receive interrupt. Frames that don't fit are discarded
+/// up until the next delimiter and reported as an error.
+#[derive(Debug, Clone)]
+pub struct FrameBuffer<const N: usize> {
+    buf: [u8; N],
+    len: usize,      // Decoded length of the current frame, even if overflown
+    pos: usize,      // Encoded length of the current frame
+    code: u8,        // Code byte of the current chunk
+    code_pos: usize, // Position of the code byte of the current chunk
+    remaining: u8,   // Data bytes left in the current chunk
+    zero: bool,      // Whether the current chunk implies a trailing zero
+    done: bool,      // Whether a frame was just returned and needs clearing
+}
+
+impl<const N: usize> FrameBuffer<N> {
+    /// Creates an empty frame accumulator.
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            pos: 0,
+            code: 0,
+            code_pos: 0,
+            remaining: 0,
+            zero: false,
+            done: false,
+        }
+    }
+
+    /// Discards any partially received frame.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.pos = 0;
+        self.remaining = 0;
+        self.zero = false;
+        self.done = false;
+    }
+
+    /// Feeds the next byte of the stream into the accumulator. Returns `None` if
+    /// the byte did not complete a frame, otherwise the decoded frame or the
+    /// reason it was rejected. Empty frames between delimiters are skipped.
+    ///
+    /// The returned frame stays valid until the next call.
+    #[inline]
+    pub fn push(&mut self, byte: u8) -> Option<Result<&[u8], DecodeError>> {
+        // If the previous byte completed a frame, start afresh
+        if self.done {
+            self.reset();
+        }
+        if byte == 0 {
+            // Delimiter reached, skip empty frames and report on any others
+            if self.pos == 0 {
+                return None;
+            }
+            self.done = true;
+
+            if self.len > N {
+                return Some(Err(DecodeError::BufferTooSmall {
+                    have: N,
+                    want: self.len,
+                }));
+            }
+            if self.remaining > 0 {
+                return Some(Err(DecodeError::ChunkOverflow {
+                    at: self.code_pos,
+                    marker: self.code,
+                    len: self.pos,
+                }));
+            }
+            return Some(Ok(self.buf.get(..self.len).unwrap_or_default()));
+        }
+        self.pos = self.pos.saturating_add(1);
+
+        // If we're inside a chunk, consume the data byte
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.store(byte);
+            return None;
+        }
+        // Otherwise a new chunk starts, backfilling any pending zero
+        if self.zero {
+            self.store(0);
+        }
+        self.code = byte;
+        self.code_pos = self.pos - 1;
+        self.remaining = byte - 1;
+        self.zero = byte != 0xff;
+        None
+    }
+
+    /// Feeds a slice of the stream into the accumulator, stopping right after the
+    /// first byte that completes a frame. Returns the number of bytes consumed
+    /// along with the frame, if any. The caller should keep feeding the rest of
+    /// the slice until it's fully consumed.
+    ///
+    /// Frames arriving in pieces (e.g. wrapping around the end of a circular DMA
+    /// buffer) need no linearization, just feed each piece in order.
+    pub fn push_slice(&mut self, bytes: &[u8]) -> (usize, Option<Result<&[u8], DecodeError>>) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            // A delimiter completes a frame unless nothing was accumulated (or a
+            // frame was just returned, making this an empty one)
+            if byte == 0 && !self.done && self.pos > 0 {
+                return (i + 1, self.push(byte));
+            }
+            self.push(byte);
+        }
+        (bytes.len(), None)
+    }
+
+    // Appends a decoded byte to the frame, or just counts it if it overflows
+    #[inline]
+    fn store(&mut self, byte: u8) {
+        if let Some(slot) = self.buf.get_mut(self.len) {
+            *slot = byte;
+        }
+        self.len = self.len.saturating_add(1);
+    }
+}
+
+impl<const N: usize> Default for FrameBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, decode_buffer, encode, encode_buffer};
+    use rand::Rng;
+
+    // Feeds a byte stream into the accumulator, collecting all the results
+    fn feed<const N: usize>(
+        fb: &mut FrameBuffer<N>,
+        data: &[u8],
+    ) -> Vec<Result<Vec<u8>, DecodeError>> {
+        data.iter()
+            .filter_map(|&b| fb.push(b).map(|res| res.map(<[u8]>::to_vec)))
+            .collect()
+    }
+
+    #[test]
+    fn test_frame_buffer_stream() {
+        let mut rng = rand::rng();
+        let mut fb = FrameBuffer::<1024>::new();
+
+        for _ in 0..100 {
+            let data: Vec<u8> = (0..rng.random_range(0..1024))
+                .map(|_| rng.random_range(0..4))
+                .collect();
+            let mut enc = vec![0u8; encode_buffer(data.len())];
+            let len = encode(&data, &mut enc).unwrap();
+
+            let mut stream = enc[..len].to_vec();
+            stream.push(0);
+            assert_eq!(feed(&mut fb, &stream), vec![Ok(data)]);
+        }
+    }
+
+    #[test]
+    fn test_frame_buffer_wrapped() {
+        // Lay out a few frames in a ring buffer such that one of them wraps
+        let mut ring = [0u8; 16];
+        let frames: [&[u8]; 3] = [&[1, 2, 3], &[0, 4, 5, 0, 6], &[7]];
+
+        let mut pos = 9;
+        for frame in frames {
+            let mut enc = [0u8; encode_buffer(5)];
+            let len = encode(frame, &mut enc).unwrap();
+            for &b in enc[..len].iter().chain(&[0]) {
+                ring[pos % ring.len()] = b;
+                pos += 1;
+            }
+        }
+        let (second, first) = ring.split_at(9);
+        let second = &second[..pos - ring.len()];
+
+        // Feed the two halves in, collecting the frames as they complete
+        let mut fb = FrameBuffer::<8>::new();
+        let mut have = Vec::new();
+        for mut rest in [first, second] {
+            while !rest.is_empty() {
+                let (n, frame) = fb.push_slice(rest);
+                if let Some(frame) = frame {
+                    have.push(frame.unwrap().to_vec());
+                }
+                rest = &rest[n..];
+            }
+        }
+        assert_eq!(have, frames);
+    }
+
+    #[test]
+    fn test_frame_buffer_errors() {
+        let mut fb = FrameBuffer::<4>::new();
+
+        // Empty frames are skipped, truncated chunks are rejected like decode
+        let stream = [0x00, 0x00, 0x03, 0x01, 0x00];
+        let mut dec = [0u8; 2];
+        let want = decode(&stream[2..4], &mut dec[..decode_buffer(2)]).unwrap_err();
+        assert_eq!(feed(&mut fb, &stream), vec![Err(want)]);
+
+        // Oversized frames are dropped, but the stream recovers right after
+        let stream = [0x06, 1, 2, 3, 4, 5, 0x00, 0x02, 7, 0x00];
+        assert_eq!(
+            feed(&mut fb, &stream),
+            vec![
+                Err(DecodeError::BufferTooSmall { have: 4, want: 5 }),
+                Ok(vec![7])
+            ]
+        );
+    }
+}
@@ -0,0 +1,156 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+use crate::DecodeError;
+
+/// A single chunk of a COBS encoded frame: a code byte followed by the run of
+/// non-zero bytes it covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk<'a> {
+    /// Position of the code byte within the encoded frame.
+    pub offset: usize,
+    /// The code byte itself, one more than the length of the data run.
+    pub code: u8,
+    /// The non-zero data run following the code byte.
+    pub data: &'a [u8],
+    /// Whether the decoded output has a zero byte following the data run.
+    pub implies_zero: bool,
+}
+
+/// Iterator over the chunks of a COBS encoded frame, created by [`chunks`].
+#[derive(Debug, Clone)]
+pub struct Chunks<'a> {
+    data: &'a [u8],
+    pos: usize,
+    done: bool,
+}
+
+/// Splits a COBS encoded frame into its chunks without copying anything. The
+/// iterator validates the frame as it walks it and yields the same errors as
+/// [`decode`](crate::decode) would, after which it stops.
+#[inline]
+pub fn chunks(data: &[u8]) -> Chunks<'_> {
+    Chunks {
+        data,
+        pos: 0,
+        done: false,
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<Chunk<'a>, DecodeError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        // The empty blob is not a valid COBS encoding
+        if self.data.is_empty() {
+            self.done = true;
+            return Some(Err(DecodeError::EmptyInput));
+        }
+        if self.pos >= self.data.len() {
+            self.done = true;
+            return None;
+        }
+        // Zero cannot be part of a COBS encoded stream
+        let offset = self.pos;
+        let code = self.data[offset];
+        if code == 0 {
+            self.done = true;
+            return Some(Err(DecodeError::ZeroMarker { at: offset }));
+        }
+        // If the marker defines an overflowing chunk, abort
+        let end = offset + code as usize;
+        if end > self.data.len() {
+            self.done = true;
+            return Some(Err(DecodeError::ChunkOverflow {
+                at: offset,
+                marker: code,
+                len: self.data.len(),
+            }));
+        }
+        // Ensure there's no zero within the chunk
+        let data = &self.data[offset + 1..end];
+        if let Some(i) = data.iter().position(|&b| b == 0) {
+            self.done = true;
+            return Some(Err(DecodeError::ZeroBinary { at: offset + 1 + i }));
+        }
+        self.pos = end;
+
+        // If we had a partial chunk, there must be a zero following
+        Some(Ok(Chunk {
+            offset,
+            code,
+            data,
+            implies_zero: end < self.data.len() && code != 0xff,
+        }))
+    }
+}
+
+impl core::iter::FusedIterator for Chunks<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, decode_buffer, encode, encode_buffer};
+
+    // Reassembles the decoded payload from the chunks, or returns the error
+    fn reassemble(data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        let mut out = Vec::new();
+        for chunk in chunks(data) {
+            let chunk = chunk?;
+            out.extend_from_slice(chunk.data);
+            if chunk.implies_zero {
+                out.push(0);
+            }
+        }
+        Ok(out)
+    }
+
+    #[test]
+    fn test_chunks_structure() {
+        let data = [0, 1, 0, 2, 0, 0, 3];
+        let mut enc = [0u8; encode_buffer(7)];
+        let len = encode(&data, &mut enc).unwrap();
+
+        let chunks: Vec<_> = chunks(&enc[..len]).map(Result::unwrap).collect();
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(chunks[0].offset, 0);
+        assert_eq!(chunks[0].code, 1);
+        assert!(chunks[0].data.is_empty() && chunks[0].implies_zero);
+        assert_eq!(chunks[1].offset, 1);
+        assert_eq!(chunks[1].data, &[1]);
+        assert_eq!(chunks[4].data, &[3]);
+        assert!(!chunks[4].implies_zero);
+    }
+
+    #[test]
+    fn test_chunks_matches_decode() {
+        let inputs: [&[u8]; 9] = [
+            &[],
+            &[0x01],
+            &[0x00],
+            &[0x03, 0x01],
+            &[0x02, 0x00, 0x01],
+            &[0x02, 0x01, 0x00],
+            &[0x01, 0x01, 0x01],
+            &[0xff; 255],
+            &[0x05, 0x01, 0x02, 0x03, 0x04, 0x02, 0x05],
+        ];
+        for input in inputs {
+            let mut dec = vec![0u8; decode_buffer(input.len())];
+            let want = decode(input, &mut dec).map(|n| dec[..n].to_vec());
+            assert_eq!(reassemble(input), want, "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_chunks_roundtrip_long() {
+        let data: Vec<u8> = (0..2000u32).map(|i| (i % 251) as u8).collect();
+        let mut enc = vec![0u8; encode_buffer(data.len())];
+        let len = encode(&data, &mut enc).unwrap();
+        assert_eq!(reassemble(&enc[..len]).unwrap(), data);
+    }
+}
@@ -0,0 +1,266 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Async framing of 0x00 delimited COBS frames via [`tokio_util::codec`].
+
+use crate::io::DEFAULT_MAX_FRAME;
+use crate::{DecodeError, decode_buffer, decode_unsafe, encode_buffer, encode_delimited};
+use bytes::{Buf, BytesMut};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Codec encoding payloads into 0x00 delimited COBS frames and decoding them
+/// back, for use with `FramedRead`, `FramedWrite` and `Framed`.
+///
+/// Malformed and oversized frames are yielded as items rather than errors, since
+/// errors terminate the framed stream, while a corrupt frame on a link usually
+/// shouldn't.
+#[derive(Debug, Clone)]
+pub struct CobsCodec {
+    max_frame: usize, // Maximum decoded size of an accepted frame
+    searched: usize,  // Bytes of the buffer already known to contain no delimiter
+    discarded: usize, // Bytes dropped from the current frame for being oversized
+}
+
+impl CobsCodec {
+    /// Creates a codec accepting frames of up to [`DEFAULT_MAX_FRAME`] decoded
+    /// bytes.
+    pub fn new() -> Self {
+        Self::with_max_frame(DEFAULT_MAX_FRAME)
+    }
+
+    /// Creates a codec accepting frames of up to `max_frame` decoded bytes.
+    pub fn with_max_frame(max_frame: usize) -> Self {
+        Self {
+            max_frame,
+            searched: 0,
+            discarded: 0,
+        }
+    }
+}
+
+impl Default for CobsCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for CobsCodec {
+    type Item = Result<Vec<u8>, DecodeError>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, io::Error> {
+        loop {
+            // If there's no delimiter yet, wait for more data, dropping it if it
+            // grows beyond what a valid frame could take
+            let Some(i) = src[self.searched..].iter().position(|&b| b == 0) else {
+                if self.discarded > 0 || src.len() > encode_buffer(self.max_frame) {
+                    self.discarded += src.len();
+                    src.clear();
+                    self.searched = 0;
+                } else {
+                    self.searched = src.len();
+                }
+                return Ok(None);
+            };
+            let end = self.searched + i;
+            self.searched = 0;
+
+            // Frame delimited, reject it if oversized, otherwise decode it
+            if self.discarded > 0 {
+                let want = decode_buffer(self.discarded + end);
+                self.discarded = 0;
+                src.advance(end + 1);
+                return Ok(Some(Err(DecodeError::BufferTooSmall {
+                    have: self.max_frame,
+                    want,
+                })));
+            }
+            if end == 0 {
+                src.advance(1);
+                continue;
+            }
+            let frame = src.split_to(end + 1);
+            let mut decoded = vec![0u8; decode_buffer(end)];
+            let result = decode_unsafe(&frame[..end], &mut decoded).and_then(|len| {
+                if len > self.max_frame {
+                    return Err(DecodeError::BufferTooSmall {
+                        have: self.max_frame,
+                        want: len,
+                    });
+                }
+                decoded.truncate(len);
+                Ok(decoded)
+            });
+            return Ok(Some(result));
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, io::Error> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() && self.discarded == 0 => Ok(None),
+            None => {
+                // Report the torn frame once, ending cleanly afterwards
+                src.clear();
+                self.searched = 0;
+                self.discarded = 0;
+                Err(io::ErrorKind::UnexpectedEof.into())
+            }
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for CobsCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, payload: T, dst: &mut BytesMut) -> Result<(), io::Error> {
+        let payload = payload.as_ref();
+        let start = dst.len();
+
+        dst.resize(start + encode_buffer(payload.len()) + 1, 0);
+        let len = encode_delimited(payload, &mut dst[start..]);
+        dst.truncate(start + len);
+        Ok(())
+    }
+}
+
+/// Async reader splitting a byte stream into 0x00 delimited COBS frames and
+/// decoding them, the counterpart of [`FrameReader`](crate::io::FrameReader)
+/// for when a whole `FramedRead` is not needed.
+///
+/// Malformed or oversized frames are reported as [`io::ErrorKind::InvalidData`]
+/// errors wrapping the [`DecodeError`]. The reader stays usable afterwards,
+/// resuming with the frame after the bad one.
+#[derive(Debug)]
+pub struct AsyncFrameReader<R: AsyncRead + Unpin> {
+    inner: R,
+    codec: CobsCodec,
+    buf: BytesMut,
+    eof: bool, // Whether the stream ended, with only buffered data left
+}
+
+impl<R: AsyncRead + Unpin> AsyncFrameReader<R> {
+    /// Creates a frame reader on top of a byte stream, accepting frames of up
+    /// to [`DEFAULT_MAX_FRAME`] decoded bytes.
+    pub fn new(inner: R) -> Self {
+        Self::with_max_frame(inner, DEFAULT_MAX_FRAME)
+    }
+
+    /// Creates a frame reader on top of a byte stream, accepting frames of up
+    /// to `max_frame` decoded bytes.
+    pub fn with_max_frame(inner: R, max_frame: usize) -> Self {
+        Self {
+            inner,
+            codec: CobsCodec::with_max_frame(max_frame),
+            buf: BytesMut::new(),
+            eof: false,
+        }
+    }
+
+    /// Reads the next frame from the stream, skipping empty ones. Returns `None`
+    /// if the stream ended cleanly on a frame boundary. A frame torn by the end
+    /// of the stream is reported once as an [`io::ErrorKind::UnexpectedEof`]
+    /// error, followed by `None`.
+    pub async fn read_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let frame = if self.eof {
+                self.codec.decode_eof(&mut self.buf)?
+            } else {
+                self.codec.decode(&mut self.buf)?
+            };
+            match frame {
+                Some(Ok(frame)) => return Ok(Some(frame)),
+                Some(Err(err)) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+                None if self.eof => return Ok(None),
+                None => self.eof = self.inner.read_buf(&mut self.buf).await? == 0,
+            }
+        }
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    #[tokio::test]
+    async fn test_codec_roundtrip() {
+        let payloads: Vec<Vec<u8>> = (0..50).map(|i| vec![(i % 3) as u8; i * 13]).collect();
+
+        let (client, server) = tokio::io::duplex(64);
+        let mut writer = FramedWrite::new(client, CobsCodec::new());
+        let mut reader = FramedRead::new(server, CobsCodec::new());
+
+        let sent = payloads.clone();
+        let sender = tokio::spawn(async move {
+            for payload in sent {
+                writer.send(payload).await.unwrap();
+            }
+        });
+        for payload in &payloads {
+            assert_eq!(&reader.next().await.unwrap().unwrap().unwrap(), payload);
+        }
+        sender.await.unwrap();
+        assert!(reader.next().await.is_none());
+    }
+
+    #[test]
+    fn test_codec_errors() {
+        let mut codec = CobsCodec::with_max_frame(16);
+        let mut src = BytesMut::from(&[0x00, 0x03, 0x01, 0x00][..]);
+        src.extend_from_slice(&[0x09; 40]);
+
+        assert!(matches!(
+            codec.decode(&mut src),
+            Ok(Some(Err(DecodeError::ChunkOverflow { .. })))
+        ));
+        assert!(matches!(codec.decode(&mut src), Ok(None)));
+
+        src.extend_from_slice(&[0x00, 0x02, 0x07, 0x00]);
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Err(DecodeError::BufferTooSmall { have: 16, want: 39 }))
+        );
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Ok(vec![0x07])));
+    }
+
+    #[tokio::test]
+    async fn test_async_frame_reader() {
+        let stream: &[u8] = &[0x02, 0x07, 0x00, 0x03, 0x01, 0x00, 0x01, 0x00, 0x02];
+        let mut reader = AsyncFrameReader::new(stream);
+
+        assert_eq!(reader.read_frame().await.unwrap(), Some(vec![0x07]));
+        assert_eq!(
+            reader.read_frame().await.unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(reader.read_frame().await.unwrap(), Some(vec![]));
+        assert_eq!(
+            reader.read_frame().await.unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(reader.read_frame().await.unwrap(), None);
+
+        // Streams cut in an oversized frame end the same way
+        let stream: &[u8] = &[0x09; 40];
+        let mut reader = AsyncFrameReader::with_max_frame(stream, 16);
+        assert_eq!(
+            reader.read_frame().await.unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(reader.read_frame().await.unwrap(), None);
+    }
+}
@@ -0,0 +1,392 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Per-frame compression in front of COBS stuffing.
+//!
+//! Each frame starts with a flag byte denoting how the rest of it was compressed.
+//! Payloads that don't shrink are sent raw, so the overhead is never more than
+//! the single flag byte.
+//!
+//! Two algorithms are available: a heatshrink-style LZSS with a 256 byte window
+//! that needs no memory beyond the frame buffers, suitable for microcontrollers,
+//! and (with the `lz4` feature) LZ4 for fast compression on hosts.
+
+use crate::stuffer::Stuffer;
+use crate::{DecodeError, EncodeError, decode, encode_buffer};
+
+/// Number of bits of a backreference's offset, defining the window size.
+const WINDOW_BITS: u32 = 8;
+
+/// Number of bits of a backreference's length, defining the lookahead size.
+const LOOKAHEAD_BITS: u32 = 4;
+
+/// Shortest match worth encoding as a backreference.
+const MIN_MATCH: usize = 2;
+
+const WINDOW_SIZE: usize = 1 << WINDOW_BITS;
+const MAX_MATCH: usize = MIN_MATCH + (1 << LOOKAHEAD_BITS) - 1;
+
+/// Compression algorithms that can be applied to a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Sends the payload uncompressed.
+    None,
+    /// heatshrink-style LZSS, slow but tiny.
+    Lzss,
+    /// LZ4 block compression, fast but needs an allocator.
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl Algorithm {
+    // Flag byte identifying the algorithm on the wire
+    fn flag(self) -> u8 {
+        match self {
+            Algorithm::None => 0x00,
+            Algorithm::Lzss => 0x01,
+            #[cfg(feature = "lz4")]
+            Algorithm::Lz4 => 0x02,
+        }
+    }
+}
+
+/// Error types that can be returned from decoding compressed frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum DecompressError {
+    #[error("decode failed: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("unknown compression flag {0:#04x}")]
+    UnknownAlgorithm(u8),
+    #[error("corrupt compressed data")]
+    Corrupt,
+    #[error("decompressed size exceeds the limit of {limit} bytes")]
+    TooLarge { limit: usize },
+}
+
+/// Computes the maximum size needed to compress and COBS encode a blind input
+/// blob.
+#[inline]
+pub const fn encode_buffer_compressed(size: usize) -> usize {
+    encode_buffer(size + 1)
+}
+
+/// Compresses an opaque data blob and encodes it with COBS using 0 as the
+/// sentinel value. If compression doesn't make the payload smaller, it is sent
+/// raw instead. Returns the number of bytes the encoding took. Returns an error
+/// if the output buffer is too small.
+pub fn encode_compressed(
+    data: &[u8],
+    algorithm: Algorithm,
+    encoded: &mut [u8],
+) -> Result<usize, EncodeError> {
+    let want = encode_buffer_compressed(data.len());
+    if encoded.len() < want {
+        return Err(EncodeError::BufferTooSmall {
+            have: encoded.len(),
+            want,
+        });
+    }
+    match algorithm {
+        Algorithm::None => {}
+        Algorithm::Lzss => {
+            let mut stuffer = Stuffer::new(&mut *encoded);
+            stuffer.push(algorithm.flag());
+            if lzss_compress(data, &mut stuffer) {
+                return Ok(stuffer.finish());
+            }
+        }
+        #[cfg(feature = "lz4")]
+        Algorithm::Lz4 => {
+            let compressed = lz4_flex::block::compress(data);
+            if compressed.len() < data.len() {
+                let mut stuffer = Stuffer::new(&mut *encoded);
+                stuffer.push(algorithm.flag());
+                stuffer.extend(&compressed);
+                return Ok(stuffer.finish());
+            }
+        }
+    }
+    // Compression was not requested or did not help, send the data raw
+    let mut stuffer = Stuffer::new(encoded);
+    stuffer.push(Algorithm::None.flag());
+    stuffer.extend(data);
+    Ok(stuffer.finish())
+}
+
+/// Decodes a COBS encoded frame and decompresses it. The frame is first decoded
+/// into `scratch`, which needs [`decode_buffer`](crate::decode_buffer) bytes,
+/// then decompressed into `decoded`, whose length caps the decompressed size.
+/// Returns the number of bytes decompressed. Returns an error if the input is
+/// malformed or decompresses to more than the output buffer can hold.
+pub fn decode_compressed(
+    data: &[u8],
+    scratch: &mut [u8],
+    decoded: &mut [u8],
+) -> Result<usize, DecompressError> {
+    let len = decode(data, scratch)?;
+    let Some((&flag, payload)) = scratch[..len].split_first() else {
+        return Err(DecompressError::Corrupt);
+    };
+    let limit = decoded.len();
+    match flag {
+        0x00 => {
+            if payload.len() > limit {
+                return Err(DecompressError::TooLarge { limit });
+            }
+            decoded[..payload.len()].copy_from_slice(payload);
+            Ok(payload.len())
+        }
+        0x01 => lzss_decompress(payload, decoded),
+        #[cfg(feature = "lz4")]
+        0x02 => lz4_flex::block::decompress_into(payload, decoded).map_err(|err| match err {
+            lz4_flex::block::DecompressError::OutputTooSmall { .. } => {
+                DecompressError::TooLarge { limit }
+            }
+            _ => DecompressError::Corrupt,
+        }),
+        flag => Err(DecompressError::UnknownAlgorithm(flag)),
+    }
+}
+
+/// MSB-first bit packer feeding whole bytes into a COBS stuffer, bailing out if
+/// the output would reach a size limit.
+struct BitWriter<'a, 'b> {
+    stuffer: &'a mut Stuffer<&'b mut [u8]>,
+    acc: u32,
+    bits: u32,
+    written: usize,
+    limit: usize,
+}
+
+impl BitWriter<'_, '_> {
+    // Appends the low `count` bits of `value`, returning false if the limit was
+    // reached
+    #[inline]
+    fn write(&mut self, value: u32, count: u32) -> bool {
+        self.acc = (self.acc << count) | (value & ((1 << count) - 1));
+        self.bits += count;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            if self.written == self.limit {
+                return false;
+            }
+            self.stuffer.push((self.acc >> self.bits) as u8);
+            self.written += 1;
+        }
+        true
+    }
+
+    // Pads the last partial byte with zeroes, returning false if the limit was
+    // reached
+    #[inline]
+    fn flush(&mut self) -> bool {
+        if self.bits == 0 {
+            return true;
+        }
+        self.write(0, 8 - self.bits)
+    }
+}
+
+/// MSB-first bit reader over a byte slice.
+struct BitReader<'a> {
+    data: &'a [u8],
+    bit: usize,
+}
+
+impl BitReader<'_> {
+    // Returns the number of bits left to read
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.bit
+    }
+
+    // Reads the next `count` bits, the caller must ensure there are enough left
+    #[inline]
+    fn read(&mut self, count: u32) -> u32 {
+        let mut value = 0u32;
+        for _ in 0..count {
+            value = (value << 1) | ((self.data[self.bit / 8] >> (7 - self.bit % 8)) & 1) as u32;
+            self.bit += 1;
+        }
+        value
+    }
+}
+
+// Compresses the data with LZSS straight into a stuffer. Returns false if the
+// compressed stream would not be smaller than the input, in which case the
+// stuffer contains garbage.
+fn lzss_compress(data: &[u8], stuffer: &mut Stuffer<&mut [u8]>) -> bool {
+    let mut writer = BitWriter {
+        stuffer,
+        acc: 0,
+        bits: 0,
+        written: 0,
+        limit: data.len().saturating_sub(1),
+    };
+    let mut i = 0;
+    while i < data.len() {
+        // Find the longest match within the window, overlaps allowed
+        let (mut best_len, mut best_off) = (0, 0);
+        for off in 1..=i.min(WINDOW_SIZE) {
+            let max = MAX_MATCH.min(data.len() - i);
+            let len = (0..max)
+                .take_while(|&k| data[i + k - off] == data[i + k])
+                .count();
+            if len > best_len {
+                (best_len, best_off) = (len, off);
+                if len == max {
+                    break;
+                }
+            }
+        }
+        // Emit either a backreference or a literal
+        let ok = if best_len >= MIN_MATCH {
+            i += best_len;
+            writer.write(0, 1)
+                && writer.write((best_off - 1) as u32, WINDOW_BITS)
+                && writer.write((best_len - MIN_MATCH) as u32, LOOKAHEAD_BITS)
+        } else {
+            i += 1;
+            writer.write(1, 1) && writer.write(data[i - 1] as u32, 8)
+        };
+        if !ok {
+            return false;
+        }
+    }
+    writer.flush()
+}
+
+// Decompresses an LZSS stream into the output buffer, whose length caps the
+// decompressed size.
+fn lzss_decompress(data: &[u8], decoded: &mut [u8]) -> Result<usize, DecompressError> {
+    let mut reader = BitReader { data, bit: 0 };
+    let limit = decoded.len();
+    let mut out = 0;
+
+    // Anything shorter than a literal is just the padding of the last byte
+    while reader.remaining() >= 9 {
+        if reader.read(1) == 1 {
+            if out == limit {
+                return Err(DecompressError::TooLarge { limit });
+            }
+            decoded[out] = reader.read(8) as u8;
+            out += 1;
+            continue;
+        }
+        if reader.remaining() < (WINDOW_BITS + LOOKAHEAD_BITS) as usize {
+            return Err(DecompressError::Corrupt);
+        }
+        let off = reader.read(WINDOW_BITS) as usize + 1;
+        let len = reader.read(LOOKAHEAD_BITS) as usize + MIN_MATCH;
+        if off > out {
+            return Err(DecompressError::Corrupt);
+        }
+        if out + len > limit {
+            return Err(DecompressError::TooLarge { limit });
+        }
+        for k in out..out + len {
+            decoded[k] = decoded[k - off];
+        }
+        out += len;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_buffer;
+    use rand::Rng;
+
+    // Returns the algorithms available in the current build
+    fn algorithms() -> Vec<Algorithm> {
+        vec![
+            Algorithm::None,
+            Algorithm::Lzss,
+            #[cfg(feature = "lz4")]
+            Algorithm::Lz4,
+        ]
+    }
+
+    // Compresses and encodes a payload into a frame
+    fn frame(data: &[u8], algorithm: Algorithm) -> Vec<u8> {
+        let mut enc = vec![0u8; encode_buffer_compressed(data.len())];
+        let len = encode_compressed(data, algorithm, &mut enc).unwrap();
+        enc.truncate(len);
+        enc
+    }
+
+    #[test]
+    fn test_compressed_roundtrip() {
+        let mut rng = rand::rng();
+        let log = b"INFO telemetry: temp=21.5C hum=40% bat=3.91V\n".repeat(20);
+        let random: Vec<u8> = (0..1000).map(|_| rng.random()).collect();
+
+        for data in [&[][..], &[0], b"a", &log, &random, &[0u8; 5000]] {
+            for algorithm in algorithms() {
+                let enc = frame(data, algorithm);
+                assert!(enc.len() <= encode_buffer_compressed(data.len()));
+
+                let mut scratch = vec![0u8; decode_buffer(enc.len())];
+                let mut dec = vec![0u8; data.len()];
+                let n = decode_compressed(&enc, &mut scratch, &mut dec).unwrap();
+                assert_eq!(&dec[..n], data, "algorithm {algorithm:?}");
+            }
+        }
+        // Compressible data must actually shrink, incompressible must go raw
+        for algorithm in &algorithms()[1..] {
+            assert!(frame(&log, *algorithm).len() < log.len() / 4);
+            assert_eq!(frame(&random, *algorithm), frame(&random, Algorithm::None));
+        }
+    }
+
+    #[test]
+    fn test_compressed_bomb_limit() {
+        let data = [0x55u8; 10000];
+        for algorithm in algorithms() {
+            let enc = frame(&data, algorithm);
+
+            let mut scratch = vec![0u8; decode_buffer(enc.len())];
+            let mut dec = vec![0u8; 1000];
+            assert_eq!(
+                decode_compressed(&enc, &mut scratch, &mut dec),
+                Err(DecompressError::TooLarge { limit: 1000 })
+            );
+        }
+    }
+
+    #[test]
+    fn test_compressed_corrupt() {
+        let mut scratch = [0u8; 16];
+        let mut dec = [0u8; 16];
+
+        // Unknown flags and broken framing are rejected
+        assert_eq!(
+            decode_compressed(&[0x02, 0x7f], &mut scratch, &mut dec),
+            Err(DecompressError::UnknownAlgorithm(0x7f))
+        );
+        assert_eq!(
+            decode_compressed(&[0x04, 0x01, 0x00, 0x00], &mut scratch, &mut dec),
+            Err(DecompressError::Decode(DecodeError::ZeroBinary { at: 2 }))
+        );
+        // So are backreferences reaching before the start of the output, be it
+        // still empty (an all-zero LZSS stream)...
+        assert_eq!(
+            decode_compressed(&[0x02, 0x01, 0x01, 0x01], &mut scratch, &mut dec),
+            Err(DecompressError::Corrupt)
+        );
+
+        // ...or holding a literal 'A', after which a backreference of 2 bytes at
+        // offset 1 is fine, whereas the same one at offset 2 is not
+        let mut frame = [0u8; 8];
+        let len = crate::encode(&[0x01, 0xa0, 0x80, 0x00], &mut frame).unwrap();
+        let n = decode_compressed(&frame[..len], &mut scratch, &mut dec).unwrap();
+        assert_eq!(&dec[..n], b"AAA");
+
+        let len = crate::encode(&[0x01, 0xa0, 0x80, 0x40], &mut frame).unwrap();
+        assert_eq!(
+            decode_compressed(&frame[..len], &mut scratch, &mut dec),
+            Err(DecompressError::Corrupt)
+        );
+    }
+}
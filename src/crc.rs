@@ -0,0 +1,403 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Checksum protected COBS frames.
+//!
+//! COBS finds frame boundaries but does not detect bit errors. The functions in
+//! this module append a checksum to the payload before stuffing it, computing
+//! it in the same pass as the encoding, and verify it after decoding.
+
+use crate::stuffer::Stuffer;
+use crate::{DecodeError, EncodeError, decode, encode_buffer};
+
+/// Error types that can be returned from decoding checksum protected frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ChecksumError {
+    #[error("decode failed: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("checksum mismatch")]
+    Mismatch,
+}
+
+/// Running checksum that can be appended to COBS frames.
+pub trait Checksum {
+    /// Number of checksum bytes appended to each frame. Must be at most 8, as
+    /// the checksum is carried in a `u64`.
+    const SIZE: usize;
+
+    /// Resets the checksum to its initial state.
+    fn reset(&mut self);
+
+    /// Folds a byte into the running checksum.
+    fn update(&mut self, byte: u8);
+
+    /// Returns the checksum of all the folded bytes, right aligned.
+    fn finalize(&self) -> u64;
+}
+
+/// Model parameters and lookup table of a 16-bit CRC, meant to be kept in a
+/// `static` and shared by the [`Crc16`] handles computing it.
+#[derive(Debug)]
+pub struct Crc16Params {
+    table: [u16; 256],
+    init: u16,
+    reflect: bool,
+    xorout: u16,
+}
+
+impl Crc16Params {
+    /// Creates a CRC from its Rocksoft model parameters, the polynomial given in
+    /// its normal (non-reversed) form. Input and output reflection are assumed
+    /// to be the same, as is the case for all common CRCs.
+    pub const fn new(poly: u16, init: u16, reflect: bool, xorout: u16) -> Self {
+        let mut table = [0u16; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc;
+            if reflect {
+                let poly = poly.reverse_bits();
+                crc = i as u16;
+                let mut bit = 0;
+                while bit < 8 {
+                    crc = if crc & 1 != 0 {
+                        (crc >> 1) ^ poly
+                    } else {
+                        crc >> 1
+                    };
+                    bit += 1;
+                }
+            } else {
+                crc = (i as u16) << 8;
+                let mut bit = 0;
+                while bit < 8 {
+                    crc = if crc & 0x8000 != 0 {
+                        (crc << 1) ^ poly
+                    } else {
+                        crc << 1
+                    };
+                    bit += 1;
+                }
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        let init = if reflect { init.reverse_bits() } else { init };
+        Self {
+            table,
+            init,
+            reflect,
+            xorout,
+        }
+    }
+}
+
+// Parameters of the predefined CRCs, in statics so their tables exist only once
+static CRC16_CCITT_FALSE: Crc16Params = Crc16Params::new(0x1021, 0xffff, false, 0x0000);
+static CRC16_XMODEM: Crc16Params = Crc16Params::new(0x1021, 0x0000, false, 0x0000);
+static CRC16_KERMIT: Crc16Params = Crc16Params::new(0x1021, 0x0000, true, 0x0000);
+
+/// Table driven 16-bit CRC. It only references its parameters and table, so
+/// it's cheap to pass around by value.
+#[derive(Debug, Clone, Copy)]
+pub struct Crc16 {
+    params: &'static Crc16Params,
+    crc: u16,
+}
+
+impl Crc16 {
+    /// CRC-16/CCITT-FALSE (a.k.a. CRC-16/IBM-3740).
+    pub const CCITT_FALSE: Crc16 = Crc16::new(&CRC16_CCITT_FALSE);
+
+    /// CRC-16/XMODEM.
+    pub const XMODEM: Crc16 = Crc16::new(&CRC16_XMODEM);
+
+    /// CRC-16/KERMIT (a.k.a. CRC-16/CCITT-TRUE).
+    pub const KERMIT: Crc16 = Crc16::new(&CRC16_KERMIT);
+
+    /// Creates a CRC computing the given algorithm.
+    pub const fn new(params: &'static Crc16Params) -> Self {
+        Self {
+            params,
+            crc: params.init,
+        }
+    }
+}
+
+impl Checksum for Crc16 {
+    const SIZE: usize = 2;
+
+    #[inline]
+    fn reset(&mut self) {
+        self.crc = self.params.init;
+    }
+
+    #[inline]
+    fn update(&mut self, byte: u8) {
+        let table = &self.params.table;
+        self.crc = if self.params.reflect {
+            (self.crc >> 8) ^ table[((self.crc ^ byte as u16) & 0xff) as usize]
+        } else {
+            (self.crc << 8) ^ table[(((self.crc >> 8) ^ byte as u16) & 0xff) as usize]
+        };
+    }
+
+    #[inline]
+    fn finalize(&self) -> u64 {
+        (self.crc ^ self.params.xorout) as u64
+    }
+}
+
+/// Model parameters and lookup table of a 32-bit CRC, meant to be kept in a
+/// `static` and shared by the [`Crc32`] handles computing it.
+#[derive(Debug)]
+pub struct Crc32Params {
+    table: [u32; 256],
+    init: u32,
+    reflect: bool,
+    xorout: u32,
+}
+
+impl Crc32Params {
+    /// Creates a CRC from its Rocksoft model parameters, the polynomial given in
+    /// its normal (non-reversed) form. Input and output reflection are assumed
+    /// to be the same, as is the case for all common CRCs.
+    pub const fn new(poly: u32, init: u32, reflect: bool, xorout: u32) -> Self {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc;
+            if reflect {
+                let poly = poly.reverse_bits();
+                crc = i as u32;
+                let mut bit = 0;
+                while bit < 8 {
+                    crc = if crc & 1 != 0 {
+                        (crc >> 1) ^ poly
+                    } else {
+                        crc >> 1
+                    };
+                    bit += 1;
+                }
+            } else {
+                crc = (i as u32) << 24;
+                let mut bit = 0;
+                while bit < 8 {
+                    crc = if crc & 0x80000000 != 0 {
+                        (crc << 1) ^ poly
+                    } else {
+                        crc << 1
+                    };
+                    bit += 1;
+                }
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        let init = if reflect { init.reverse_bits() } else { init };
+        Self {
+            table,
+            init,
+            reflect,
+            xorout,
+        }
+    }
+}
+
+// Parameters of the predefined CRCs, in statics so their tables exist only once
+static CRC32_ISO_HDLC: Crc32Params = Crc32Params::new(0x04c11db7, 0xffffffff, true, 0xffffffff);
+static CRC32_CASTAGNOLI: Crc32Params = Crc32Params::new(0x1edc6f41, 0xffffffff, true, 0xffffffff);
+
+/// Table driven 32-bit CRC. It only references its parameters and table, so
+/// it's cheap to pass around by value.
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    params: &'static Crc32Params,
+    crc: u32,
+}
+
+impl Crc32 {
+    /// CRC-32/ISO-HDLC, the one used by Ethernet, zlib and most everything else.
+    pub const ISO_HDLC: Crc32 = Crc32::new(&CRC32_ISO_HDLC);
+
+    /// CRC-32/ISCSI (a.k.a. CRC-32C, Castagnoli).
+    pub const CASTAGNOLI: Crc32 = Crc32::new(&CRC32_CASTAGNOLI);
+
+    /// Creates a CRC computing the given algorithm.
+    pub const fn new(params: &'static Crc32Params) -> Self {
+        Self {
+            params,
+            crc: params.init,
+        }
+    }
+}
+
+impl Checksum for Crc32 {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn reset(&mut self) {
+        self.crc = self.params.init;
+    }
+
+    #[inline]
+    fn update(&mut self, byte: u8) {
+        let table = &self.params.table;
+        self.crc = if self.params.reflect {
+            (self.crc >> 8) ^ table[((self.crc ^ byte as u32) & 0xff) as usize]
+        } else {
+            (self.crc << 8) ^ table[(((self.crc >> 24) ^ byte as u32) & 0xff) as usize]
+        };
+    }
+
+    #[inline]
+    fn finalize(&self) -> u64 {
+        (self.crc ^ self.params.xorout) as u64
+    }
+}
+
+/// Computes the maximum size needed to COBS encode a blind input blob along with
+/// its checksum.
+#[inline]
+pub const fn encode_buffer_checked<C: Checksum>(size: usize) -> usize {
+    encode_buffer(size + C::SIZE)
+}
+
+/// Encodes an opaque data blob with COBS using 0 as the sentinel value, with a
+/// checksum appended (most significant byte first). The checksum is computed in
+/// the same pass as the stuffing. Returns the number of bytes the encoding took.
+/// Returns an error if the output buffer is too small.
+pub fn encode_checked<C: Checksum>(
+    data: &[u8],
+    mut checksum: C,
+    encoded: &mut [u8],
+) -> Result<usize, EncodeError> {
+    const { assert!(C::SIZE <= 8, "checksums are at most 8 bytes") };
+
+    let want = encode_buffer_checked::<C>(data.len());
+    if encoded.len() < want {
+        return Err(EncodeError::BufferTooSmall {
+            have: encoded.len(),
+            want,
+        });
+    }
+    checksum.reset();
+
+    let mut stuffer = Stuffer::new(encoded);
+    for &b in data {
+        checksum.update(b);
+        stuffer.push(b);
+    }
+    stuffer.extend(&checksum.finalize().to_be_bytes()[8 - C::SIZE..]);
+    Ok(stuffer.finish())
+}
+
+/// Decodes a COBS encoded frame with a trailing checksum and verifies it. Returns
+/// the length of the payload, which is placed at the start of `decoded`. Returns
+/// an error if the output buffer is too small, if the input is malformed or if
+/// the checksum does not match.
+///
+/// The output buffer must have room for the checksum too, which is what
+/// [`decode_buffer`](crate::decode_buffer) returns anyway.
+pub fn decode_checked<C: Checksum>(
+    data: &[u8],
+    mut checksum: C,
+    decoded: &mut [u8],
+) -> Result<usize, ChecksumError> {
+    const { assert!(C::SIZE <= 8, "checksums are at most 8 bytes") };
+
+    let len = decode(data, decoded)?;
+    if len < C::SIZE {
+        return Err(ChecksumError::Mismatch);
+    }
+    let (payload, sum) = decoded[..len].split_at(len - C::SIZE);
+
+    checksum.reset();
+    for &b in payload {
+        checksum.update(b);
+    }
+    if checksum.finalize().to_be_bytes()[8 - C::SIZE..] != *sum {
+        return Err(ChecksumError::Mismatch);
+    }
+    Ok(payload.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_buffer, encode};
+    use rand::Rng;
+
+    // Computes a checksum over a blob in one go
+    fn digest<C: Checksum>(mut checksum: C, data: &[u8]) -> u64 {
+        checksum.reset();
+        data.iter().for_each(|&b| checksum.update(b));
+        checksum.finalize()
+    }
+
+    #[test]
+    fn test_check_values() {
+        assert_eq!(digest(Crc16::CCITT_FALSE, b"123456789"), 0x29b1);
+        assert_eq!(digest(Crc16::XMODEM, b"123456789"), 0x31c3);
+        assert_eq!(digest(Crc16::KERMIT, b"123456789"), 0x2189);
+        assert_eq!(digest(Crc32::ISO_HDLC, b"123456789"), 0xcbf43926);
+        assert_eq!(digest(Crc32::CASTAGNOLI, b"123456789"), 0xe3069283);
+
+        // Custom CRCs keep their table in a static too, handles stay small
+        static CRC16_ARC: Crc16Params = Crc16Params::new(0x8005, 0x0000, true, 0x0000);
+        assert_eq!(digest(Crc16::new(&CRC16_ARC), b"123456789"), 0xbb3d);
+        assert!(core::mem::size_of::<Crc32>() <= 2 * core::mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn test_checked_roundtrip() {
+        let mut rng = rand::rng();
+        for size in [0, 1, 252, 253, 254, 255, 1000] {
+            let data: Vec<u8> = (0..size).map(|_| rng.random_range(0..4)).collect();
+
+            // The fused encoding must match encoding the payload with its CRC
+            let mut plain = data.clone();
+            plain.extend_from_slice(&(digest(Crc32::ISO_HDLC, &data) as u32).to_be_bytes());
+            let mut want = vec![0u8; encode_buffer(plain.len())];
+            let want_len = encode(&plain, &mut want).unwrap();
+
+            let mut enc = vec![0u8; encode_buffer_checked::<Crc32>(size)];
+            let len = encode_checked(&data, Crc32::ISO_HDLC, &mut enc).unwrap();
+            assert_eq!(&enc[..len], &want[..want_len]);
+
+            let mut dec = vec![0u8; decode_buffer(len)];
+            let n = decode_checked(&enc[..len], Crc32::ISO_HDLC, &mut dec).unwrap();
+            assert_eq!(&dec[..n], &data[..]);
+        }
+    }
+
+    #[test]
+    fn test_checked_corruption() {
+        let data = b"hello checksummed world";
+        let mut enc = [0u8; encode_buffer_checked::<Crc16>(23)];
+        let len = encode_checked(data, Crc16::CCITT_FALSE, &mut enc).unwrap();
+
+        // Flip every bit of every data byte that keeps the framing intact
+        for i in 1..len {
+            for bit in 0..8 {
+                let mut bad = enc;
+                bad[i] ^= 1 << bit;
+                if bad[i] == 0 {
+                    continue;
+                }
+                let mut dec = [0u8; decode_buffer(encode_buffer_checked::<Crc16>(23))];
+                assert!(decode_checked(&bad[..len], Crc16::CCITT_FALSE, &mut dec).is_err());
+            }
+        }
+        // Frames too short to even hold a checksum are rejected too
+        let mut dec = [0u8; 1];
+        assert_eq!(
+            decode_checked(&[0x02, 0x01], Crc16::CCITT_FALSE, &mut dec),
+            Err(ChecksumError::Mismatch)
+        );
+        // While broken framing is reported as such
+        assert!(matches!(
+            decode_checked(&[0x03, 0x01], Crc16::CCITT_FALSE, &mut dec),
+            Err(ChecksumError::Decode(DecodeError::ChunkOverflow { .. }))
+        ));
+    }
+}
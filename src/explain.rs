@@ -0,0 +1,149 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+use crate::{DecodeError, chunks};
+use std::ops::Range;
+
+/// A run of a COBS encoded frame as laid out on the wire, including the ones
+/// cut short by an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    /// Position of the code byte within the encoded frame.
+    pub offset: usize,
+    /// The code byte itself, one more than the length of the data run.
+    pub code: u8,
+    /// Positions of the data bytes within the encoded frame.
+    pub data: Range<usize>,
+    /// Position of the first data byte within the decoded payload.
+    pub decoded: usize,
+    /// Whether the decoded output has a zero byte following the data run.
+    pub implies_zero: bool,
+    /// Whether the run is a full block of 254 non-zero bytes, which is followed
+    /// by another code byte without any zero in between.
+    pub full_block: bool,
+    /// Whether the run is incomplete because the frame is malformed there.
+    pub broken: bool,
+}
+
+/// Structure of an encoded frame, as walked by the decoder, created by
+/// [`explain`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// Runs of the frame in order, up to and including the malformed one.
+    pub runs: Vec<Run>,
+    /// The payload decoded up to the point of the error.
+    pub decoded: Vec<u8>,
+    /// The error the decoder would fail with, if any.
+    pub error: Option<DecodeError>,
+}
+
+/// Walks an encoded frame (without the 0x00 delimiter) and lays out its runs,
+/// the decoded payload and the reason it fails to decode, if it does. This is
+/// meant for debugging broken frames, not for speed.
+pub fn explain(encoded: &[u8]) -> Explanation {
+    let mut runs = Vec::new();
+    let mut decoded = Vec::new();
+
+    for chunk in chunks(encoded) {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                // Lay out whatever part of the run is there before the error
+                let end = match err {
+                    DecodeError::ZeroBinary { at } => Some(at),
+                    DecodeError::ChunkOverflow { .. } => Some(encoded.len()),
+                    _ => None,
+                };
+                if let Some(end) = end {
+                    let offset = runs.last().map_or(0, |r: &Run| r.data.end);
+                    let data = offset + 1..end;
+
+                    runs.push(Run {
+                        offset,
+                        code: encoded[offset],
+                        data: data.clone(),
+                        decoded: decoded.len(),
+                        implies_zero: false,
+                        full_block: false,
+                        broken: true,
+                    });
+                    decoded.extend_from_slice(&encoded[data]);
+                }
+                return Explanation {
+                    runs,
+                    decoded,
+                    error: Some(err),
+                };
+            }
+        };
+        let start = chunk.offset + 1;
+        runs.push(Run {
+            offset: chunk.offset,
+            code: chunk.code,
+            data: start..start + chunk.data.len(),
+            decoded: decoded.len(),
+            implies_zero: chunk.implies_zero,
+            full_block: chunk.code == 0xff,
+            broken: false,
+        });
+        decoded.extend_from_slice(chunk.data);
+        if chunk.implies_zero {
+            decoded.push(0);
+        }
+    }
+    Explanation {
+        runs,
+        decoded,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, decode_buffer};
+
+    #[test]
+    fn test_explain_valid() {
+        let mut encoded = vec![0x03, 0x11, 0x22, 0x01, 0xff];
+        encoded.extend(1..=254u8);
+        encoded.extend([0x02, 0x33]);
+
+        let exp = explain(&encoded);
+        assert_eq!(exp.error, None);
+        assert_eq!(exp.runs.len(), 4);
+        assert_eq!(exp.runs[0].data, 1..3);
+        assert!(exp.runs[1].implies_zero && exp.runs[1].data.is_empty());
+        assert_eq!(exp.runs[1].decoded, 3);
+        assert!(exp.runs[2].full_block && !exp.runs[2].implies_zero);
+        assert_eq!((exp.runs[3].offset, exp.runs[3].decoded), (259, 258));
+
+        let mut dec = vec![0u8; decode_buffer(encoded.len())];
+        let len = decode(&encoded, &mut dec).unwrap();
+        assert_eq!(exp.decoded, &dec[..len]);
+    }
+
+    #[test]
+    fn test_explain_broken() {
+        let exp = explain(&[0x02, 0x11, 0x04, 0x22, 0x00, 0x33]);
+        assert_eq!(exp.error, Some(DecodeError::ZeroBinary { at: 4 }));
+        assert_eq!(exp.runs[1].data, 3..4);
+        assert!(exp.runs[1].broken);
+        assert_eq!(exp.decoded, [0x11, 0x00, 0x22]);
+
+        let exp = explain(&[0x02, 0x11, 0x05, 0x22]);
+        assert!(matches!(
+            exp.error,
+            Some(DecodeError::ChunkOverflow { at: 2, .. })
+        ));
+        assert_eq!(exp.runs[1].data, 3..4);
+
+        let exp = explain(&[0x01, 0x00]);
+        assert_eq!(exp.error, Some(DecodeError::ZeroMarker { at: 1 }));
+        assert_eq!(exp.runs.len(), 1);
+
+        let exp = explain(&[]);
+        assert_eq!(exp.error, Some(DecodeError::EmptyInput));
+        assert!(exp.runs.is_empty());
+    }
+}
@@ -0,0 +1,439 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Reed-Solomon forward error correction for COBS frames.
+//!
+//! The payload is split into blocks of up to `255 - 2t` bytes, each of which is
+//! followed by `2t` parity bytes before the whole frame is stuffed. After decoding
+//! the frame, up to `t` corrupted bytes can be corrected in every block.
+//!
+//! Only corruption of the data bytes can be corrected. A corrupted code byte (or
+//! a data byte corrupted into a zero) breaks the COBS framing itself, which the
+//! error correction cannot recover from.
+
+use crate::stuffer::Stuffer;
+use crate::{DecodeError, EncodeError, decode, encode_buffer};
+
+/// Exponent table of GF(2^8) over the primitive polynomial 0x11d, doubled up to
+/// avoid reducing the exponent sums modulo 255.
+const EXP: [u8; 512] = {
+    let mut exp = [0u8; 512];
+    let mut x = 1u16;
+    let mut i = 0;
+    while i < 512 {
+        exp[i] = x as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11d;
+        }
+        i += 1;
+    }
+    exp
+};
+
+/// Logarithm table of GF(2^8), with the undefined log(0) set to zero.
+const LOG: [u8; 256] = {
+    let mut log = [0u8; 256];
+    let mut i = 0;
+    while i < 255 {
+        log[EXP[i] as usize] = i as u8;
+        i += 1;
+    }
+    log
+};
+
+// Multiplies two field elements
+#[inline]
+fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+}
+
+// Divides two field elements, the divisor must be non-zero
+#[inline]
+fn div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        return 0;
+    }
+    EXP[LOG[a as usize] as usize + 255 - LOG[b as usize] as usize]
+}
+
+// Raises the field's generator to the given power
+#[inline]
+fn pow(exp: usize) -> u8 {
+    EXP[exp % 255]
+}
+
+/// Error types that can be returned from decoding error corrected frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum FecError {
+    #[error("decode failed: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("uncorrectable frame")]
+    Uncorrectable,
+}
+
+/// Counters of the frames passed through a [`FecCodec`]'s decoder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FecStats {
+    /// Frames that decoded without any corruption.
+    pub clean: u64,
+    /// Frames that had corrupted bytes, all of which were corrected.
+    pub corrected: u64,
+    /// Total number of bytes corrected across all frames.
+    pub corrected_bytes: u64,
+    /// Frames that could not be recovered, either because the COBS framing was
+    /// broken or because a block had more than `t` corrupted bytes.
+    pub uncorrectable: u64,
+}
+
+/// Reed-Solomon codec correcting up to `t` byte errors per block of a frame.
+#[derive(Debug, Clone)]
+pub struct FecCodec {
+    parity: usize,
+    generator: [u8; 255],
+    stats: FecStats,
+}
+
+impl FecCodec {
+    /// Creates a codec that can correct up to `t` byte errors in every block of
+    /// `255 - 2t` payload bytes.
+    ///
+    /// # Panics
+    /// Panics if `t` is not within `1..=126`.
+    pub fn new(t: usize) -> Self {
+        assert!(
+            (1..=126).contains(&t),
+            "correction capacity {t} out of range"
+        );
+        let parity = 2 * t;
+
+        // Build the generator polynomial (x + α^0)(x + α^1)...(x + α^(2t-1)),
+        // highest degree coefficient first
+        let mut generator = [0u8; 255];
+        generator[0] = 1;
+        for i in 0..parity {
+            let root = pow(i);
+            for j in (1..=i + 1).rev() {
+                generator[j] ^= mul(generator[j - 1], root);
+            }
+        }
+        Self {
+            parity,
+            generator,
+            stats: FecStats::default(),
+        }
+    }
+
+    /// Returns the number of payload bytes in a full block.
+    #[inline]
+    fn block_data(&self) -> usize {
+        255 - self.parity
+    }
+
+    /// Computes the maximum size needed to encode a blind input blob along with
+    /// its parity bytes.
+    pub fn encode_buffer(&self, size: usize) -> usize {
+        let blocks = size.div_ceil(self.block_data()).max(1);
+        encode_buffer(size + blocks * self.parity)
+    }
+
+    /// Returns the statistics gathered by the decoder so far.
+    #[inline]
+    pub fn stats(&self) -> FecStats {
+        self.stats
+    }
+
+    /// Encodes an opaque data blob with COBS using 0 as the sentinel value, with
+    /// Reed-Solomon parity bytes appended to each block. Returns the number of
+    /// bytes the encoding took. Returns an error if the output buffer is too
+    /// small.
+    pub fn encode(&self, data: &[u8], encoded: &mut [u8]) -> Result<usize, EncodeError> {
+        let want = self.encode_buffer(data.len());
+        if encoded.len() < want {
+            return Err(EncodeError::BufferTooSmall {
+                have: encoded.len(),
+                want,
+            });
+        }
+        let mut stuffer = Stuffer::new(encoded);
+        let mut blocks = data.chunks(self.block_data());
+        let mut block = blocks.next().unwrap_or_default();
+        loop {
+            // Compute the parity of the block via polynomial division by the
+            // generator, stuffing the data bytes as they pass
+            let mut parity = [0u8; 254];
+            let parity = &mut parity[..self.parity];
+            for &b in block {
+                stuffer.push(b);
+
+                let feedback = b ^ parity[0];
+                parity.copy_within(1.., 0);
+                parity[self.parity - 1] = 0;
+                if feedback != 0 {
+                    for (p, &g) in parity.iter_mut().zip(&self.generator[1..]) {
+                        *p ^= mul(feedback, g);
+                    }
+                }
+            }
+            stuffer.extend(parity);
+
+            match blocks.next() {
+                Some(next) => block = next,
+                None => break,
+            }
+        }
+        Ok(stuffer.finish())
+    }
+
+    /// Decodes a COBS encoded frame and corrects any errors within its blocks.
+    /// Returns the length of the payload, which is placed at the start of
+    /// `decoded`. Returns an error if the output buffer is too small, if the
+    /// input is malformed or if the corruption is beyond repair.
+    ///
+    /// The output buffer must have room for the parity bytes too, which is what
+    /// [`decode_buffer`](crate::decode_buffer) returns anyway.
+    pub fn decode(&mut self, data: &[u8], decoded: &mut [u8]) -> Result<usize, FecError> {
+        let len = match decode(data, decoded) {
+            Ok(len) => len,
+            Err(err) => {
+                if !matches!(err, DecodeError::BufferTooSmall { .. }) {
+                    self.stats.uncorrectable += 1;
+                }
+                return Err(err.into());
+            }
+        };
+        // Ensure the block layout is one the encoder could have produced
+        let tail = len % 255;
+        if len == 0 || (tail != 0 && tail < self.parity) || (tail == self.parity && len > tail) {
+            self.stats.uncorrectable += 1;
+            return Err(FecError::Uncorrectable);
+        }
+        // Correct each block in place, then compact the payloads together
+        let mut fixed = 0;
+        for block in decoded[..len].chunks_mut(255) {
+            match self.correct(block) {
+                Some(n) => fixed += n,
+                None => {
+                    self.stats.uncorrectable += 1;
+                    return Err(FecError::Uncorrectable);
+                }
+            }
+        }
+        let mut size = 0;
+        for start in (0..len).step_by(255) {
+            let end = (start + 255).min(len) - self.parity;
+            decoded.copy_within(start..end, size);
+            size += end - start;
+        }
+        if fixed > 0 {
+            self.stats.corrected += 1;
+            self.stats.corrected_bytes += fixed as u64;
+        } else {
+            self.stats.clean += 1;
+        }
+        Ok(size)
+    }
+
+    // Corrects the errors in a single block (data followed by parity). Returns
+    // the number of bytes corrected, or None if the block is beyond repair.
+    fn correct(&self, block: &mut [u8]) -> Option<usize> {
+        let n = block.len();
+        let nsym = self.parity;
+
+        // Compute the syndromes, if all are zero, the block is intact
+        let mut syndromes = [0u8; 254];
+        let syndromes = &mut syndromes[..nsym];
+        for (i, s) in syndromes.iter_mut().enumerate() {
+            let root = pow(i);
+            *s = block.iter().fold(0, |acc, &b| mul(acc, root) ^ b);
+        }
+        if syndromes.iter().all(|&s| s == 0) {
+            return Some(0);
+        }
+        // Find the error locator polynomial via Berlekamp-Massey, lowest degree
+        // coefficient first
+        let mut locator = [0u8; 255];
+        let mut prev = [0u8; 255];
+        locator[0] = 1;
+        prev[0] = 1;
+
+        let (mut errors, mut shift, mut scale) = (0usize, 1usize, 1u8);
+        for k in 0..nsym {
+            let mut delta = syndromes[k];
+            for i in 1..=errors {
+                delta ^= mul(locator[i], syndromes[k - i]);
+            }
+            if delta == 0 {
+                shift += 1;
+                continue;
+            }
+            let saved = locator;
+            let factor = div(delta, scale);
+            for i in 0..=nsym - shift {
+                locator[i + shift] ^= mul(factor, prev[i]);
+            }
+            if 2 * errors <= k {
+                errors = k + 1 - errors;
+                prev = saved;
+                scale = delta;
+                shift = 1;
+            } else {
+                shift += 1;
+            }
+        }
+        if errors > nsym / 2 {
+            return None;
+        }
+        // Compute the error evaluator Ω(x) = S(x)Λ(x) mod x^2t
+        let mut evaluator = [0u8; 254];
+        for i in 0..nsym {
+            for j in 0..=i.min(errors) {
+                evaluator[i] ^= mul(syndromes[i - j], locator[j]);
+            }
+        }
+        // Find the error positions via Chien search and fix them with Forney's
+        // algorithm. The byte at index j is the coefficient of x^(n-1-j).
+        let mut found = 0;
+        for (j, byte) in block.iter_mut().enumerate() {
+            let power = n - 1 - j;
+            let inv = pow(255 - power % 255);
+
+            // Evaluate Λ(X^-1) and its formal derivative at the same time
+            let (mut value, mut derivative, mut x) = (0u8, 0u8, 1u8);
+            for (i, &coeff) in locator[..=errors].iter().enumerate() {
+                value ^= mul(coeff, x);
+                if i % 2 == 1 {
+                    derivative ^= mul(coeff, div(x, inv));
+                }
+                x = mul(x, inv);
+            }
+            if value != 0 {
+                continue;
+            }
+            if derivative == 0 {
+                return None;
+            }
+            let mut omega = 0u8;
+            let mut x = 1u8;
+            for &e in &evaluator[..nsym] {
+                omega ^= mul(e, x);
+                x = mul(x, inv);
+            }
+            *byte ^= mul(pow(power), div(omega, derivative));
+            found += 1;
+        }
+        if found != errors {
+            return None;
+        }
+        // Ensure the correction actually produced a codeword
+        for i in 0..nsym {
+            let root = pow(i);
+            if block.iter().fold(0, |acc, &b| mul(acc, root) ^ b) != 0 {
+                return None;
+            }
+        }
+        Some(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunks, decode_buffer};
+    use rand::Rng;
+    use rand::seq::index::sample;
+
+    // Encodes a payload, returning the frame and the positions of its data bytes
+    fn frame(codec: &FecCodec, data: &[u8]) -> (Vec<u8>, Vec<usize>) {
+        let mut enc = vec![0u8; codec.encode_buffer(data.len())];
+        let len = codec.encode(data, &mut enc).unwrap();
+        enc.truncate(len);
+
+        let positions = chunks(&enc)
+            .flat_map(|c| {
+                let c = c.unwrap();
+                c.offset + 1..c.offset + c.code as usize
+            })
+            .collect();
+        (enc, positions)
+    }
+
+    #[test]
+    fn test_fec_roundtrip_clean() {
+        let mut rng = rand::rng();
+        for t in [1, 4, 16, 126] {
+            let mut codec = FecCodec::new(t);
+            for size in [0, 1, 100, 255 - 2 * t, 256 - 2 * t, 1000] {
+                let data: Vec<u8> = (0..size).map(|_| rng.random()).collect();
+                let (enc, _) = frame(&codec, &data);
+
+                let mut dec = vec![0u8; decode_buffer(enc.len())];
+                let n = codec.decode(&enc, &mut dec).unwrap();
+                assert_eq!(&dec[..n], &data[..], "t {t}, size {size}");
+            }
+            assert_eq!(codec.stats().clean, 6);
+        }
+    }
+
+    #[test]
+    fn test_fec_corrects_errors() {
+        let mut rng = rand::rng();
+        let mut codec = FecCodec::new(8);
+
+        for round in 0..200 {
+            let data: Vec<u8> = (0..rng.random_range(1..1500))
+                .map(|_| rng.random())
+                .collect();
+            let (mut enc, positions) = frame(&codec, &data);
+
+            // Corrupt up to t data bytes (the first block is the most exposed),
+            // keeping them non-zero so the framing survives
+            let errors = rng.random_range(1..=8).min(positions.len().min(255));
+            for i in sample(&mut rng, positions.len().min(255), errors) {
+                let at = positions[i];
+                enc[at] = loop {
+                    let b = rng.random_range(1..=255);
+                    if b != enc[at] {
+                        break b;
+                    }
+                };
+            }
+            let mut dec = vec![0u8; decode_buffer(enc.len())];
+            let n = codec.decode(&enc, &mut dec).unwrap();
+            assert_eq!(&dec[..n], &data[..], "round {round}");
+        }
+        let stats = codec.stats();
+        assert_eq!(stats.corrected, 200);
+        assert!(stats.corrected_bytes >= 200);
+        assert_eq!(stats.uncorrectable, 0);
+    }
+
+    #[test]
+    fn test_fec_rejects_excess_errors() {
+        let mut rng = rand::rng();
+        let mut codec = FecCodec::new(8);
+
+        let data: Vec<u8> = (0..200).map(|_| rng.random_range(1..=255)).collect();
+        let (enc, positions) = frame(&codec, &data);
+
+        let mut rejected = 0;
+        for _ in 0..100 {
+            let mut bad = enc.clone();
+            for i in sample(&mut rng, positions.len(), 20) {
+                bad[positions[i]] = bad[positions[i]] % 255 + 1;
+            }
+            let mut dec = vec![0u8; decode_buffer(bad.len())];
+            match codec.decode(&bad, &mut dec) {
+                Err(FecError::Uncorrectable) => rejected += 1,
+                Ok(n) => assert_ne!(&dec[..n], &data[..]),
+                Err(err) => panic!("unexpected error: {err}"),
+            }
+        }
+        // Miscorrection beyond capacity is possible, but must be rare
+        assert!(rejected > 90);
+        assert_eq!(codec.stats().uncorrectable, rejected);
+    }
+}
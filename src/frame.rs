@@ -0,0 +1,448 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+use crate::{DecodeError, chunks};
+use core::fmt;
+
+#[cfg(feature = "std")]
+use {
+    crate::{encode_buffer, encode_unsafe},
+    std::borrow::Borrow,
+    std::ops::Deref,
+};
+
+/// Byte string proven to contain no 0x00 bytes, so it can be put on the wire
+/// between delimiters as is. The owned counterpart is [`ZeroFreeBytes`].
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct ZeroFree([u8]);
+
+impl ZeroFree {
+    /// Checks that a byte string contains no 0x00 bytes, returning the position
+    /// of the first one otherwise.
+    pub fn new(bytes: &[u8]) -> Result<&Self, DecodeError> {
+        if let Some(at) = bytes.iter().position(|&b| b == 0) {
+            return Err(DecodeError::ZeroBinary { at });
+        }
+        Ok(Self::from_bytes_unchecked(bytes))
+    }
+
+    // Wraps a byte string already known to contain no 0x00 bytes
+    fn from_bytes_unchecked(bytes: &[u8]) -> &Self {
+        // SAFETY: ZeroFree is a transparent wrapper around [u8]
+        unsafe { &*(bytes as *const [u8] as *const Self) }
+    }
+
+    /// Returns the underlying bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the number of bytes in the string.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether the string is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for &'a ZeroFree {
+    type Error = DecodeError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        ZeroFree::new(bytes)
+    }
+}
+
+impl AsRef<[u8]> for ZeroFree {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for ZeroFree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ZeroFree({self})")
+    }
+}
+
+impl fmt::Display for ZeroFree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        hex(&self.0, f)
+    }
+}
+
+/// Byte string proven to be a valid COBS encoding, without the delimiter. It
+/// can be decoded without any further checks. The owned counterpart is
+/// [`CobsFrameBuf`].
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct CobsFrame([u8]);
+
+impl CobsFrame {
+    /// Checks that a byte string is a valid COBS encoding, returning the same
+    /// error [`decode`](crate::decode) would otherwise.
+    pub fn new(bytes: &[u8]) -> Result<&Self, DecodeError> {
+        for chunk in chunks(bytes) {
+            chunk?;
+        }
+        Ok(Self::from_bytes_unchecked(bytes))
+    }
+
+    // Wraps a byte string already known to be a valid encoding
+    fn from_bytes_unchecked(bytes: &[u8]) -> &Self {
+        // SAFETY: CobsFrame is a transparent wrapper around [u8]
+        unsafe { &*(bytes as *const [u8] as *const Self) }
+    }
+
+    /// Returns the encoded bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the encoded bytes as a zero free string, which every valid
+    /// encoding is.
+    pub fn as_zero_free(&self) -> &ZeroFree {
+        ZeroFree::from_bytes_unchecked(&self.0)
+    }
+
+    /// Returns the length of the encoded frame.
+    pub fn encoded_len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns the length of the payload the frame decodes into.
+    pub fn decoded_len(&self) -> usize {
+        chunks(&self.0)
+            .map(|chunk| {
+                let chunk = chunk.expect("validated frame");
+                chunk.data.len() + chunk.implies_zero as usize
+            })
+            .sum()
+    }
+
+    /// Decodes the frame into a caller provided buffer, returning the length of
+    /// the payload.
+    ///
+    /// # Panics
+    /// Panics if the buffer is shorter than [`decoded_len`](Self::decoded_len).
+    pub fn decode_into(&self, decoded: &mut [u8]) -> usize {
+        let mut pos = 0;
+        for chunk in chunks(&self.0) {
+            let chunk = chunk.expect("validated frame");
+            decoded[pos..pos + chunk.data.len()].copy_from_slice(chunk.data);
+            pos += chunk.data.len();
+            if chunk.implies_zero {
+                decoded[pos] = 0;
+                pos += 1;
+            }
+        }
+        pos
+    }
+
+    /// Decodes the frame into a freshly allocated payload.
+    #[cfg(feature = "std")]
+    pub fn decode(&self) -> Vec<u8> {
+        let mut decoded = vec![0u8; self.decoded_len()];
+        self.decode_into(&mut decoded);
+        decoded
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for &'a CobsFrame {
+    type Error = DecodeError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        CobsFrame::new(bytes)
+    }
+}
+
+impl AsRef<[u8]> for CobsFrame {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<ZeroFree> for CobsFrame {
+    fn as_ref(&self) -> &ZeroFree {
+        self.as_zero_free()
+    }
+}
+
+impl fmt::Debug for CobsFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CobsFrame({self})")
+    }
+}
+
+impl fmt::Display for CobsFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        hex(&self.0, f)
+    }
+}
+
+/// Owned byte string proven to contain no 0x00 bytes.
+#[cfg(feature = "std")]
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ZeroFreeBytes(Vec<u8>);
+
+#[cfg(feature = "std")]
+impl ZeroFreeBytes {
+    /// Unwraps the string, returning the underlying bytes.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<Vec<u8>> for ZeroFreeBytes {
+    type Error = DecodeError;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        ZeroFree::new(&bytes)?;
+        Ok(Self(bytes))
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<&[u8]> for ZeroFreeBytes {
+    type Error = DecodeError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(ZeroFree::new(bytes)?.to_owned())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ZeroFreeBytes> for Vec<u8> {
+    fn from(bytes: ZeroFreeBytes) -> Self {
+        bytes.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl Deref for ZeroFreeBytes {
+    type Target = ZeroFree;
+
+    fn deref(&self) -> &ZeroFree {
+        ZeroFree::from_bytes_unchecked(&self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Borrow<ZeroFree> for ZeroFreeBytes {
+    fn borrow(&self) -> &ZeroFree {
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+impl ToOwned for ZeroFree {
+    type Owned = ZeroFreeBytes;
+
+    fn to_owned(&self) -> ZeroFreeBytes {
+        ZeroFreeBytes(self.0.to_vec())
+    }
+}
+
+#[cfg(feature = "std")]
+impl AsRef<[u8]> for ZeroFreeBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for ZeroFreeBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for ZeroFreeBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// Owned byte string proven to be a valid COBS encoding, without the delimiter.
+#[cfg(feature = "std")]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CobsFrameBuf(Vec<u8>);
+
+#[cfg(feature = "std")]
+impl CobsFrameBuf {
+    /// Encodes a payload into a frame, which is valid by construction.
+    pub fn encode(data: &[u8]) -> Self {
+        let mut encoded = vec![0u8; encode_buffer(data.len())];
+        let len = encode_unsafe(data, &mut encoded);
+        encoded.truncate(len);
+        Self(encoded)
+    }
+
+    /// Unwraps the frame, returning the encoded bytes.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<Vec<u8>> for CobsFrameBuf {
+    type Error = DecodeError;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        CobsFrame::new(&bytes)?;
+        Ok(Self(bytes))
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<&[u8]> for CobsFrameBuf {
+    type Error = DecodeError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(CobsFrame::new(bytes)?.to_owned())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<CobsFrameBuf> for Vec<u8> {
+    fn from(frame: CobsFrameBuf) -> Self {
+        frame.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<CobsFrameBuf> for ZeroFreeBytes {
+    fn from(frame: CobsFrameBuf) -> Self {
+        ZeroFreeBytes(frame.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Deref for CobsFrameBuf {
+    type Target = CobsFrame;
+
+    fn deref(&self) -> &CobsFrame {
+        CobsFrame::from_bytes_unchecked(&self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Borrow<CobsFrame> for CobsFrameBuf {
+    fn borrow(&self) -> &CobsFrame {
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+impl ToOwned for CobsFrame {
+    type Owned = CobsFrameBuf;
+
+    fn to_owned(&self) -> CobsFrameBuf {
+        CobsFrameBuf(self.0.to_vec())
+    }
+}
+
+#[cfg(feature = "std")]
+impl AsRef<[u8]> for CobsFrameBuf {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for CobsFrameBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for CobsFrameBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+// Writes bytes as lowercase hex
+fn hex(bytes: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for b in bytes {
+        write!(f, "{b:02x}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_free() {
+        let bytes = ZeroFree::new(&[0x01, 0xab]).unwrap();
+        assert_eq!(bytes.as_bytes(), &[0x01, 0xab]);
+        assert_eq!(format!("{bytes}"), "01ab");
+        assert_eq!(format!("{bytes:?}"), "ZeroFree(01ab)");
+        assert!(ZeroFree::new(&[]).unwrap().is_empty());
+
+        assert_eq!(
+            <&ZeroFree>::try_from(&[0x01, 0x02, 0x00][..]),
+            Err(DecodeError::ZeroBinary { at: 2 })
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_cobs_frame() {
+        // Frames built from payloads decode back into them
+        let payloads: [Vec<u8>; 5] = [
+            vec![],
+            vec![0],
+            vec![0, 1, 0, 2, 0, 0, 3],
+            (1..=254).collect(),
+            (0..2000u32).map(|i| (i % 251) as u8).collect(),
+        ];
+        for payload in payloads {
+            let frame = CobsFrameBuf::encode(&payload);
+            assert_eq!(frame.decoded_len(), payload.len());
+            assert_eq!(frame.decode(), payload);
+            assert_eq!(CobsFrame::new(frame.as_bytes()).unwrap(), &*frame);
+            assert_eq!(frame.as_zero_free().len(), frame.encoded_len());
+        }
+        let frame = CobsFrameBuf::encode(&[0x11, 0x00]);
+        assert_eq!(format!("{frame}"), "021101");
+        assert_eq!(format!("{frame:?}"), "CobsFrame(021101)");
+
+        let owned = ZeroFreeBytes::from(frame);
+        assert_eq!(&*owned, ZeroFree::new(&[0x02, 0x11, 0x01]).unwrap());
+        assert_eq!(format!("{owned:?}"), "ZeroFree(021101)");
+        assert_eq!(
+            ZeroFreeBytes::try_from(vec![0x00]),
+            Err(DecodeError::ZeroBinary { at: 0 })
+        );
+
+        // Invalid encodings are refused with the same errors as decoding
+        let inputs: [&[u8]; 6] = [
+            &[],
+            &[0x00],
+            &[0x02, 0x00, 0x01],
+            &[0x05, 0x11],
+            &[0xff; 255],
+            &[0x05, 0x01, 0x02, 0x03, 0x04, 0x02, 0x05],
+        ];
+        for input in inputs {
+            let mut decoded = vec![0u8; crate::decode_buffer(input.len())];
+            match crate::decode(input, &mut decoded) {
+                Ok(len) => {
+                    let frame = <&CobsFrame>::try_from(input).unwrap();
+                    assert_eq!(frame.decode(), &decoded[..len]);
+                }
+                Err(err) => assert_eq!(CobsFrameBuf::try_from(input), Err(err)),
+            }
+        }
+    }
+}
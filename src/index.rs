@@ -0,0 +1,287 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+use crate::DecodeError;
+use core::ops::Range;
+
+/// Random-access index over a COBS encoded frame, mapping offsets between the
+/// encoded and decoded representations.
+///
+/// The index is built by hopping across the code bytes only, so constructing it
+/// touches a fraction of the frame. The data runs are validated lazily, when a
+/// range of them is decoded via [`decode_range`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CobsIndex {
+    chunks: Vec<IndexEntry>,
+    encoded_len: usize,
+    decoded_len: usize,
+}
+
+/// Location of a single chunk in both coordinate systems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct IndexEntry {
+    pub(crate) encoded: usize, // Position of the code byte in the encoded frame
+    pub(crate) decoded: usize, // Position of the first data byte in the decoded frame
+    pub(crate) code: u8,       // Code byte of the chunk
+}
+
+/// Hops through the code bytes of an encoded frame, recording where each chunk
+/// lands. Returns the chunks walked and their decoded length, along with the
+/// error that stopped the walk early, if any.
+pub(crate) fn walk(encoded: &[u8]) -> (Vec<IndexEntry>, usize, Option<DecodeError>) {
+    // The empty blob is not a valid COBS encoding
+    if encoded.is_empty() {
+        return (Vec::new(), 0, Some(DecodeError::EmptyInput));
+    }
+    let mut chunks = Vec::new();
+    let mut decoded = 0usize;
+    let mut i = 0usize;
+
+    while i < encoded.len() {
+        // Zero cannot be part of a COBS encoded stream
+        let code = encoded[i];
+        if code == 0 {
+            return (chunks, decoded, Some(DecodeError::ZeroMarker { at: i }));
+        }
+        // If the marker defines an overflowing chunk, abort
+        if i + code as usize > encoded.len() {
+            let err = DecodeError::ChunkOverflow {
+                at: i,
+                marker: code,
+                len: encoded.len(),
+            };
+            return (chunks, decoded, Some(err));
+        }
+        chunks.push(IndexEntry {
+            encoded: i,
+            decoded,
+            code,
+        });
+        i += code as usize;
+        decoded += code as usize - 1;
+
+        // If we had a partial chunk, there must be a zero following
+        if i < encoded.len() && code != 0xff {
+            decoded += 1;
+        }
+    }
+    (chunks, decoded, None)
+}
+
+impl CobsIndex {
+    /// Builds an index over a COBS encoded frame. Returns an error if the chain
+    /// of code bytes is malformed; zeros within data runs are not detected.
+    pub fn new(encoded: &[u8]) -> Result<Self, DecodeError> {
+        let (chunks, decoded_len, err) = walk(encoded);
+        if let Some(err) = err {
+            return Err(err);
+        }
+        Ok(Self {
+            chunks,
+            encoded_len: encoded.len(),
+            decoded_len,
+        })
+    }
+
+    /// Returns the length of the encoded frame the index was built from.
+    #[inline]
+    pub fn encoded_len(&self) -> usize {
+        self.encoded_len
+    }
+
+    /// Returns the length of the decoded payload.
+    #[inline]
+    pub fn decoded_len(&self) -> usize {
+        self.decoded_len
+    }
+
+    /// Maps a position in the decoded payload to the position of the encoded
+    /// byte representing it. Implied zeros map to the code byte of the chunk
+    /// following them. Returns `None` if the position is out of bounds.
+    pub fn encoded_offset(&self, decoded: usize) -> Option<usize> {
+        if decoded >= self.decoded_len {
+            return None;
+        }
+        let chunk = &self.chunks[self.chunk_by_decoded(decoded)];
+        Some(chunk.encoded + 1 + (decoded - chunk.decoded))
+    }
+
+    /// Maps a position in the encoded frame to the position in the decoded
+    /// payload it lands on. Code bytes map to the start of their chunk's data,
+    /// which makes this suitable for translating [`DecodeError`] positions.
+    /// Returns `None` if the position is out of bounds.
+    pub fn decoded_offset(&self, encoded: usize) -> Option<usize> {
+        if encoded >= self.encoded_len {
+            return None;
+        }
+        let idx = self.chunks.partition_point(|c| c.encoded <= encoded) - 1;
+        let chunk = &self.chunks[idx];
+        Some(chunk.decoded + (encoded - chunk.encoded).saturating_sub(1))
+    }
+
+    // Finds the index of the chunk containing a decoded position, including its
+    // implied trailing zero
+    fn chunk_by_decoded(&self, decoded: usize) -> usize {
+        // Chunks without data share their start with their successor, the last
+        // one of such a group is the one actually containing the position
+        let idx = self.chunks.partition_point(|c| c.decoded <= decoded) - 1;
+        debug_assert!(decoded - self.chunks[idx].decoded < self.chunks[idx].code as usize);
+        idx
+    }
+}
+
+/// Decodes a sub-range of a COBS encoded frame's payload, using an index built
+/// over the same frame to skip straight to the relevant chunks. Returns the
+/// number of bytes decoded, which is always the length of the range. Returns an
+/// error if the output buffer is too small or the touched chunks contain zeros.
+///
+/// # Panics
+/// Panics if the range is out of bounds of the decoded payload, or if the index
+/// was not built from `encoded`.
+pub fn decode_range(
+    encoded: &[u8],
+    index: &CobsIndex,
+    range: Range<usize>,
+    decoded: &mut [u8],
+) -> Result<usize, DecodeError> {
+    assert_eq!(
+        encoded.len(),
+        index.encoded_len,
+        "index built for a different frame"
+    );
+    assert!(
+        range.start <= range.end && range.end <= index.decoded_len,
+        "range {:?} out of bounds for decoded length {}",
+        range,
+        index.decoded_len
+    );
+    if decoded.len() < range.len() {
+        return Err(DecodeError::BufferTooSmall {
+            have: decoded.len(),
+            want: range.len(),
+        });
+    }
+    if range.is_empty() {
+        return Ok(0);
+    }
+    // Walk the chunks from the first one overlapping the range, copying data
+    // runs and backfilling the implied zeros
+    let mut output_pos = 0usize;
+    let mut pos = range.start;
+
+    for chunk in &index.chunks[index.chunk_by_decoded(range.start)..] {
+        let run = chunk.code as usize - 1;
+        let skip = pos - chunk.decoded;
+
+        if skip < run {
+            let take = (run - skip).min(range.end - pos);
+            let start = chunk.encoded + 1 + skip;
+            let data = &encoded[start..start + take];
+
+            if let Some(i) = data.iter().position(|&b| b == 0) {
+                return Err(DecodeError::ZeroBinary { at: start + i });
+            }
+            decoded[output_pos..output_pos + take].copy_from_slice(data);
+            output_pos += take;
+            pos += take;
+        }
+        if pos == range.end {
+            break;
+        }
+        // The range continues past the data run, so the run must be followed by
+        // an implied zero (otherwise the next chunk starts at the same position)
+        if chunk.decoded + run == pos && chunk.code != 0xff {
+            decoded[output_pos] = 0;
+            output_pos += 1;
+            pos += 1;
+            if pos == range.end {
+                break;
+            }
+        }
+    }
+    Ok(output_pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::random;
+    use crate::{decode, decode_buffer, encode, encode_buffer};
+    use rand::Rng;
+
+    #[test]
+    fn test_index_offsets() {
+        let mut rng = rand::rng();
+        for size in [0, 1, 10, 254, 255, 600, 5000] {
+            let data: Vec<u8> = (0..size).map(|_| rng.random_range(0..8)).collect();
+            let mut enc = vec![0u8; encode_buffer(size)];
+            let len = encode(&data, &mut enc).unwrap();
+            let enc = &enc[..len];
+
+            let index = CobsIndex::new(enc).unwrap();
+            assert_eq!(index.encoded_len(), len);
+            assert_eq!(index.decoded_len(), size);
+
+            for (i, &b) in data.iter().enumerate() {
+                let at = index.encoded_offset(i).unwrap();
+                if b != 0 {
+                    assert_eq!(enc[at], b);
+                    assert_eq!(index.decoded_offset(at), Some(i));
+                } else {
+                    assert_eq!(index.decoded_offset(at), Some(i + 1));
+                }
+            }
+            assert_eq!(index.encoded_offset(size), None);
+            assert_eq!(index.decoded_offset(len), None);
+        }
+    }
+
+    #[test]
+    fn test_decode_range() {
+        let mut rng = rand::rng();
+        for size in [1, 10, 254, 255, 508, 5000] {
+            for zeros in [0.0, 0.01, 0.3] {
+                let data = random(size, zeros);
+                let mut enc = vec![0u8; encode_buffer(size)];
+                let len = encode(&data, &mut enc).unwrap();
+                let index = CobsIndex::new(&enc[..len]).unwrap();
+
+                for _ in 0..100 {
+                    let start = rng.random_range(0..=size);
+                    let end = rng.random_range(start..=size);
+
+                    let mut out = vec![0u8; end - start];
+                    let n = decode_range(&enc[..len], &index, start..end, &mut out).unwrap();
+                    assert_eq!(&out[..n], &data[start..end]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_range_errors() {
+        // Code chain errors surface when building the index
+        for input in [&[][..], &[0x00], &[0x03, 0x01]] {
+            let mut dec = vec![0u8; decode_buffer(input.len())];
+            assert_eq!(
+                CobsIndex::new(input).unwrap_err(),
+                decode(input, &mut dec).unwrap_err()
+            );
+        }
+        // Zeros in the data surface only when the range touches them
+        let enc = [0x03, 0x01, 0x02, 0x03, 0x00, 0x04];
+        let index = CobsIndex::new(&enc).unwrap();
+
+        let mut out = [0u8; 6];
+        assert_eq!(decode_range(&enc, &index, 0..3, &mut out), Ok(3));
+        assert_eq!(&out[..3], &[1, 2, 0]);
+        assert_eq!(
+            decode_range(&enc, &index, 0..4, &mut out),
+            Err(DecodeError::ZeroBinary { at: 4 })
+        );
+        assert_eq!(
+            decode_range(&enc, &index, 0..4, &mut out[..2]),
+            Err(DecodeError::BufferTooSmall { have: 2, want: 4 })
+        );
+    }
+}
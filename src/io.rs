@@ -0,0 +1,245 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Blocking adapters for reading and writing 0x00 delimited COBS frames over
+//! [`std::io`] streams.
+
+use crate::{
+    CobsFrame, DecodeError, decode_buffer, decode_unsafe, encode_buffer, encode_delimited,
+};
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+/// Default cap on the decoded size of frames accepted by a [`FrameReader`].
+pub const DEFAULT_MAX_FRAME: usize = 64 * 1024;
+
+/// Writer encoding payloads into 0x00 delimited COBS frames.
+#[derive(Debug)]
+pub struct FrameWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> FrameWriter<W> {
+    /// Creates a frame writer on top of a byte stream.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Encodes a payload and writes it out as a single delimited frame.
+    pub fn write_frame(&mut self, payload: &[u8]) -> io::Result<()> {
+        self.buf.resize(encode_buffer(payload.len()) + 1, 0);
+        let len = encode_delimited(payload, &mut self.buf);
+        self.inner.write_all(&self.buf[..len])
+    }
+
+    /// Writes out an already encoded frame, followed by the delimiter.
+    pub fn write_encoded(&mut self, frame: &CobsFrame) -> io::Result<()> {
+        self.buf.clear();
+        self.buf.extend_from_slice(frame.as_bytes());
+        self.buf.push(0);
+        self.inner.write_all(&self.buf)
+    }
+
+    /// Flushes the underlying stream.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwraps the writer, returning the underlying stream.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Reader splitting a byte stream into 0x00 delimited COBS frames and decoding
+/// them.
+///
+/// Malformed or oversized frames are reported as [`io::ErrorKind::InvalidData`]
+/// errors wrapping the [`DecodeError`]. The reader stays usable afterwards,
+/// resuming with the frame after the bad one.
+#[derive(Debug)]
+pub struct FrameReader<R: Read> {
+    inner: BufReader<R>,
+    frame: Vec<u8>,   // Encoded bytes of the frame being accumulated
+    max_frame: usize, // Maximum decoded size of an accepted frame
+    discarded: usize, // Bytes dropped from the current frame for being oversized
+}
+
+impl<R: Read> FrameReader<R> {
+    /// Creates a frame reader on top of a byte stream, accepting frames of up
+    /// to [`DEFAULT_MAX_FRAME`] decoded bytes.
+    pub fn new(inner: R) -> Self {
+        Self::with_max_frame(inner, DEFAULT_MAX_FRAME)
+    }
+
+    /// Creates a frame reader on top of a byte stream, accepting frames of up
+    /// to `max_frame` decoded bytes.
+    pub fn with_max_frame(inner: R, max_frame: usize) -> Self {
+        Self {
+            inner: BufReader::new(inner),
+            frame: Vec::new(),
+            max_frame,
+            discarded: 0,
+        }
+    }
+
+    /// Reads the next frame from the stream, skipping empty ones. Returns `None`
+    /// if the stream ended cleanly on a frame boundary. A frame torn by the end
+    /// of the stream is reported once as an [`io::ErrorKind::UnexpectedEof`]
+    /// error, followed by `None`.
+    pub fn read_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let available = self.inner.fill_buf()?;
+            if available.is_empty() {
+                if self.frame.is_empty() && self.discarded == 0 {
+                    return Ok(None);
+                }
+                // Report the torn frame once, ending cleanly afterwards
+                self.frame.clear();
+                self.discarded = 0;
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            // Accumulate everything up to the next delimiter, dropping the data
+            // if it grows beyond what a valid frame could take
+            let (chunk, delimited) = match available.iter().position(|&b| b == 0) {
+                Some(i) => (&available[..i], true),
+                None => (available, false),
+            };
+            if self.discarded > 0 || self.frame.len() + chunk.len() > encode_buffer(self.max_frame)
+            {
+                self.discarded += self.frame.len() + chunk.len();
+                self.frame.clear();
+            } else {
+                self.frame.extend_from_slice(chunk);
+            }
+            let consumed = chunk.len() + delimited as usize;
+            self.inner.consume(consumed);
+
+            if !delimited {
+                continue;
+            }
+            // Frame delimited, reject it if oversized, otherwise decode it
+            if self.discarded > 0 {
+                let want = decode_buffer(self.discarded);
+                self.discarded = 0;
+                return Err(invalid(DecodeError::BufferTooSmall {
+                    have: self.max_frame,
+                    want,
+                }));
+            }
+            if self.frame.is_empty() {
+                continue;
+            }
+            let mut decoded = vec![0u8; decode_buffer(self.frame.len())];
+            let result = decode_unsafe(&self.frame, &mut decoded);
+            self.frame.clear();
+
+            let len = result.map_err(invalid)?;
+            if len > self.max_frame {
+                return Err(invalid(DecodeError::BufferTooSmall {
+                    have: self.max_frame,
+                    want: len,
+                }));
+            }
+            decoded.truncate(len);
+            return Ok(Some(decoded));
+        }
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+}
+
+impl<R: Read> Iterator for FrameReader<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+// Wraps a decoding error into an I/O error
+fn invalid(err: DecodeError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_io_roundtrip() {
+        let payloads: Vec<Vec<u8>> = (0..50).map(|i| vec![(i % 3) as u8; i * 13]).collect();
+
+        // Alternate between encoding on the fly and writing pre-encoded frames
+        let mut writer = FrameWriter::new(Vec::new());
+        for (i, payload) in payloads.iter().enumerate() {
+            if i % 2 == 0 {
+                writer.write_frame(payload).unwrap();
+            } else {
+                writer
+                    .write_encoded(&crate::CobsFrameBuf::encode(payload))
+                    .unwrap();
+            }
+        }
+        let stream = writer.into_inner();
+
+        // Read back with a tiny buffer to exercise frames spanning many reads
+        let reader = FrameReader::new(io::BufReader::with_capacity(3, &stream[..]));
+        let frames: Vec<Vec<u8>> = reader.map(Result::unwrap).collect();
+        assert_eq!(frames, payloads);
+    }
+
+    #[test]
+    fn test_frame_io_errors() {
+        let mut stream = vec![0x00, 0x03, 0x01, 0x00];
+        stream.extend([0x09; 40]);
+        stream.extend([0x00, 0x02, 0x07, 0x00, 0x02]);
+
+        let mut reader = FrameReader::with_max_frame(&stream[..], 16);
+
+        let err = reader.read_frame().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = reader.read_frame().unwrap_err();
+        assert_eq!(
+            err.into_inner().unwrap().downcast_ref::<DecodeError>(),
+            Some(&DecodeError::BufferTooSmall { have: 16, want: 39 })
+        );
+        assert_eq!(reader.read_frame().unwrap(), Some(vec![0x07]));
+        assert_eq!(
+            reader.read_frame().unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(reader.read_frame().unwrap(), None);
+
+        // Iterating over streams cut mid-frame (or mid-oversized-frame) ends
+        let results: Vec<_> = FrameReader::with_max_frame(&stream[..], 16).collect();
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            results[3].as_ref().unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        let results: Vec<_> = FrameReader::with_max_frame(&stream[..44], 16).collect();
+        assert_eq!(results.len(), 2);
+    }
+}
@@ -0,0 +1,233 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+use crate::DecodeError;
+use core::iter::{FusedIterator, Peekable};
+
+/// Lazy COBS encoder over a byte iterator, created by [`encode_iter`].
+#[derive(Debug, Clone)]
+pub struct EncodeIter<I: Iterator<Item = u8>> {
+    source: Peekable<I>,
+    block: [u8; 254],
+    len: usize, // Number of data bytes in the current block
+    pos: usize, // Next data byte to emit, 1 based (the code byte goes first)
+    code: u8,   // Code byte of the current block
+    open: bool, // Whether another chunk needs to be emitted
+}
+
+/// Encodes a byte stream with COBS using 0 as the sentinel value, yielding the
+/// encoded bytes lazily. At most one 254 byte block is buffered at any time and
+/// the output is identical to [`encode`](crate::encode).
+#[inline]
+pub fn encode_iter<I: IntoIterator<Item = u8>>(data: I) -> EncodeIter<I::IntoIter> {
+    EncodeIter {
+        source: data.into_iter().peekable(),
+        block: [0; 254],
+        len: 0,
+        pos: 1,
+        code: 0,
+        open: true,
+    }
+}
+
+impl<I: Iterator<Item = u8>> Iterator for EncodeIter<I> {
+    type Item = u8;
+
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        // If the current block is not yet drained, keep emitting it
+        if self.pos <= self.len {
+            let b = self.block[self.pos - 1];
+            self.pos += 1;
+            return Some(b);
+        }
+        if !self.open {
+            return None;
+        }
+        // Gather the next block of non-zero bytes, terminated by a zero, by the
+        // end of the input or by the block filling up
+        self.len = 0;
+        loop {
+            match self.source.next() {
+                Some(0) => {
+                    self.code = self.len as u8 + 1;
+                    break;
+                }
+                Some(b) => {
+                    self.block[self.len] = b;
+                    self.len += 1;
+
+                    // If an entire chunk was non-zero, only open another one
+                    // if there's more data coming
+                    if self.len == 254 {
+                        self.code = 0xff;
+                        self.open = self.source.peek().is_some();
+                        break;
+                    }
+                }
+                None => {
+                    self.code = self.len as u8 + 1;
+                    self.open = false;
+                    break;
+                }
+            }
+        }
+        self.pos = 1;
+        Some(self.code)
+    }
+}
+
+impl<I: Iterator<Item = u8>> FusedIterator for EncodeIter<I> {}
+
+/// Lazy COBS decoder over a byte iterator, created by [`decode_iter`].
+#[derive(Debug, Clone)]
+pub struct DecodeIter<I: Iterator<Item = u8>> {
+    source: Peekable<I>,
+    pos: usize,      // Position of the next input byte
+    code: u8,        // Code byte of the current chunk
+    code_pos: usize, // Position of the code byte of the current chunk
+    remaining: u8,   // Data bytes left in the current chunk
+    zero: bool,      // Whether the current chunk implies a trailing zero
+    done: bool,      // Whether the stream finished or failed
+}
+
+/// Decodes a COBS encoded byte stream using 0 as the sentinel value, yielding
+/// the decoded bytes lazily. The output is identical to [`decode`](crate::decode)
+/// for well formed input.
+///
+/// Errors are detected as the bytes arrive, so anything preceding a malformed
+/// chunk is yielded before the error itself. After an error, the iterator is
+/// exhausted.
+#[inline]
+pub fn decode_iter<I: IntoIterator<Item = u8>>(data: I) -> DecodeIter<I::IntoIter> {
+    DecodeIter {
+        source: data.into_iter().peekable(),
+        pos: 0,
+        code: 0,
+        code_pos: 0,
+        remaining: 0,
+        zero: false,
+        done: false,
+    }
+}
+
+impl<I: Iterator<Item = u8>> DecodeIter<I> {
+    // Terminates the iteration with the given error
+    #[inline]
+    fn fail(&mut self, err: DecodeError) -> Option<Result<u8, DecodeError>> {
+        self.done = true;
+        Some(Err(err))
+    }
+}
+
+impl<I: Iterator<Item = u8>> Iterator for DecodeIter<I> {
+    type Item = Result<u8, DecodeError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        // If we're inside a chunk, consume it, ensuring there's no zero in it
+        if self.remaining > 0 {
+            return match self.source.next() {
+                Some(0) => self.fail(DecodeError::ZeroBinary { at: self.pos }),
+                Some(b) => {
+                    self.pos += 1;
+                    self.remaining -= 1;
+                    Some(Ok(b))
+                }
+                None => self.fail(DecodeError::ChunkOverflow {
+                    at: self.code_pos,
+                    marker: self.code,
+                    len: self.pos,
+                }),
+            };
+        }
+        // Chunk finished, if there's nothing more, the stream is done (unless
+        // it didn't even start, in which case it's invalid)
+        if self.source.peek().is_none() {
+            if self.pos == 0 {
+                return self.fail(DecodeError::EmptyInput);
+            }
+            self.done = true;
+            return None;
+        }
+        // If we had a partial chunk, there must be a zero following
+        if self.zero {
+            self.zero = false;
+            return Some(Ok(0));
+        }
+        // Start the next chunk, zero cannot be a code byte
+        let code = self.source.next().unwrap_or_default();
+        if code == 0 {
+            return self.fail(DecodeError::ZeroMarker { at: self.pos });
+        }
+        self.code = code;
+        self.code_pos = self.pos;
+        self.pos += 1;
+        self.remaining = code - 1;
+        self.zero = code != 0xff;
+
+        self.next()
+    }
+}
+
+impl<I: Iterator<Item = u8>> FusedIterator for DecodeIter<I> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::random;
+    use crate::{decode, decode_buffer, encode, encode_buffer};
+    use rand::Rng;
+
+    #[test]
+    fn test_encode_iter_matches_encode() {
+        for size in [0, 1, 2, 253, 254, 255, 256, 507, 508, 509, 1000] {
+            for zeros in [0.0, 0.01, 0.5] {
+                let data = random(size, zeros);
+
+                let mut enc = vec![0u8; encode_buffer(size)];
+                let len = encode(&data, &mut enc).unwrap();
+
+                let iter: Vec<u8> = encode_iter(data.iter().copied()).collect();
+                assert_eq!(iter, &enc[..len], "input {:?}", data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_iter_roundtrip() {
+        let mut rng = rand::rng();
+        for size in [0, 1, 2, 253, 254, 255, 256, 507, 508, 509, 1000] {
+            let data: Vec<u8> = (0..size).map(|_| rng.random_range(0..4)).collect();
+            let encoded: Vec<u8> = encode_iter(data.iter().copied()).collect();
+
+            let decoded: Result<Vec<u8>, _> = decode_iter(encoded).collect();
+            assert_eq!(decoded.unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_decode_iter_errors() {
+        let inputs: [&[u8]; 6] = [
+            &[],
+            &[0x00],
+            &[0x03, 0x01],
+            &[0x02, 0x00, 0x01],
+            &[0x02, 0x01, 0x00],
+            &[0x01, 0x01, 0x01],
+        ];
+        for input in inputs {
+            let mut dec = vec![0u8; decode_buffer(input.len())];
+            let want = decode(input, &mut dec).map(|n| dec[..n].to_vec());
+            let have: Result<Vec<u8>, _> = decode_iter(input.iter().copied()).collect();
+            assert_eq!(have, want, "input {:?}", input);
+        }
+        // Streams ending before the first code byte are rejected right away
+        let mut iter = decode_iter(core::iter::empty());
+        assert_eq!(iter.next(), Some(Err(DecodeError::EmptyInput)));
+        assert_eq!(iter.next(), None);
+    }
+}
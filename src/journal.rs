@@ -0,0 +1,546 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Append-only on-disk log of COBS framed records.
+//!
+//! Every record is a single 0x00 delimited frame written with one call, so a
+//! crash can only ever tear the frame at the tail of the file. Reopening a log
+//! drops anything after the last intact record, zero filled blocks included,
+//! and the delimiters let readers walk the records from either end.
+//!
+//! Records are self-describing: a flag byte in front of the data tells whether
+//! a timestamp precedes it and whether a CRC-32C follows it, so logs written
+//! with different configurations can be read back alike.
+
+use crate::crc::{Crc32, decode_checked, encode_buffer_checked, encode_checked};
+use crate::{DecodeError, decode, decode_buffer, encode};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Take, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Flags of the record header byte
+const FLAG_TIMESTAMP: u8 = 0x01;
+const FLAG_CHECKSUM: u8 = 0x02;
+
+// Size of the blocks read when scanning the log backwards
+const BLOCK_SIZE: u64 = 4096;
+
+/// Policy of flushing appended records to stable storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Leave flushing to the OS (or explicit [`FrameLog::sync`] calls).
+    Never,
+    /// Sync the data after every record.
+    Always,
+    /// Sync the data after every so many records.
+    Every(u32),
+}
+
+/// Configuration of the records appended to a [`FrameLog`].
+#[derive(Debug, Clone, Copy)]
+pub struct LogConfig {
+    /// Whether to protect records with a CRC-32C.
+    pub checksum: bool,
+    /// Whether to stamp records with the time of appending.
+    pub timestamps: bool,
+    /// When to sync appended records to stable storage.
+    pub sync: SyncPolicy,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            checksum: true,
+            timestamps: false,
+            sync: SyncPolicy::Always,
+        }
+    }
+}
+
+/// Record read back from a [`FrameLog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// Position of the encoded record within the log file.
+    pub offset: u64,
+    /// Time of appending, if the record was stamped.
+    pub timestamp: Option<SystemTime>,
+    /// Data of the record.
+    pub data: Vec<u8>,
+}
+
+/// Append-only log of COBS framed records in a file.
+#[derive(Debug)]
+pub struct FrameLog {
+    path: PathBuf,
+    file: File,
+    config: LogConfig,
+    len: u64,       // Length of the log, up to the last delimiter
+    truncated: u64, // Bytes of torn tail dropped when opening
+    unsynced: u32,  // Records appended since the last sync
+    poisoned: bool, // Whether a failed append could not be rolled back
+    buf: Vec<u8>,   // Scratch space for assembling records
+}
+
+impl FrameLog {
+    /// Opens a log file, creating it if missing. A torn frame at the end of the
+    /// file, left behind by an interrupted append, is truncated away. So is a
+    /// last record that fails to decode or to verify even though it appears to
+    /// be delimited, as filesystems may zero fill the blocks of a torn write.
+    pub fn open<P: AsRef<Path>>(path: P, config: LogConfig) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::options()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+
+        let size = file.metadata()?.len();
+        let len = recover(&mut file, size)?;
+        if len < size {
+            file.set_len(len)?;
+            file.sync_all()?;
+        }
+        Ok(Self {
+            path,
+            file,
+            config,
+            len,
+            truncated: size - len,
+            unsynced: 0,
+            poisoned: false,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Returns the number of bytes of torn tail dropped when opening the log.
+    pub fn truncated(&self) -> u64 {
+        self.truncated
+    }
+
+    /// Returns the length of the log file in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns whether the log holds no records.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends a record to the log, syncing it as the policy dictates. Returns
+    /// the offset of the record within the log file.
+    ///
+    /// If the record fails to be written, whatever part of it made it into the
+    /// file is truncated away. Should that fail too, the log refuses further
+    /// appends until it's reopened, which recovers the torn tail.
+    pub fn append(&mut self, data: &[u8]) -> io::Result<u64> {
+        if self.poisoned {
+            return Err(io::Error::other(
+                "log tail torn by a failed append, reopen to recover",
+            ));
+        }
+        let flags = (self.config.checksum as u8 * FLAG_CHECKSUM)
+            | (self.config.timestamps as u8 * FLAG_TIMESTAMP);
+
+        let mut payload = Vec::with_capacity(9 + data.len());
+        payload.push(flags);
+        if self.config.timestamps {
+            let micros = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_micros() as u64);
+            payload.extend_from_slice(&micros.to_be_bytes());
+        }
+        payload.extend_from_slice(data);
+
+        // Assemble the whole frame so it hits the file in a single write
+        self.buf
+            .resize(encode_buffer_checked::<Crc32>(payload.len()) + 1, 0);
+        let len = if self.config.checksum {
+            encode_checked(&payload, Crc32::CASTAGNOLI, &mut self.buf)
+        } else {
+            encode(&payload, &mut self.buf)
+        }
+        .expect("buffer sized for checksum");
+        self.buf[len] = 0;
+        if let Err(err) = self.file.write_all(&self.buf[..len + 1]) {
+            self.rollback();
+            return Err(err);
+        }
+
+        let offset = self.len;
+        self.len += len as u64 + 1;
+        self.unsynced += 1;
+
+        match self.config.sync {
+            SyncPolicy::Never => {}
+            SyncPolicy::Always => self.sync()?,
+            SyncPolicy::Every(n) if self.unsynced >= n => self.sync()?,
+            SyncPolicy::Every(_) => {}
+        }
+        Ok(offset)
+    }
+
+    // Drops whatever part of a failed append made it into the file, so the next
+    // record doesn't get glued onto a torn frame
+    fn rollback(&mut self) {
+        if self.file.set_len(self.len).is_err() {
+            self.poisoned = true;
+        }
+    }
+
+    /// Syncs all appended records to stable storage.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    /// Iterates over the records from the oldest to the newest. Records that
+    /// are appended after the iterator is created are not visited.
+    pub fn iter(&self) -> io::Result<Iter> {
+        let file = File::open(&self.path)?;
+        Ok(Iter {
+            reader: BufReader::new(file.take(self.len)),
+            offset: 0,
+        })
+    }
+
+    /// Iterates over the records from the newest to the oldest.
+    pub fn iter_rev(&self) -> io::Result<IterRev> {
+        Ok(IterRev {
+            file: File::open(&self.path)?,
+            start: self.len,
+            tail: Vec::new(),
+        })
+    }
+}
+
+/// Forward iterator over the records of a [`FrameLog`], created by
+/// [`FrameLog::iter`].
+///
+/// Malformed or corrupted records are reported as [`io::ErrorKind::InvalidData`]
+/// errors wrapping the [`DecodeError`] or [`ChecksumError`](crate::crc::ChecksumError),
+/// after which iteration carries on.
+#[derive(Debug)]
+pub struct Iter {
+    reader: BufReader<Take<File>>,
+    offset: u64, // Position of the next frame in the log
+}
+
+impl Iterator for Iter {
+    type Item = io::Result<LogRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut frame = Vec::new();
+        loop {
+            frame.clear();
+            let n = match self.reader.read_until(0, &mut frame) {
+                Ok(0) => return None,
+                Ok(n) => n,
+                Err(err) => return Some(Err(err)),
+            };
+            let offset = self.offset;
+            self.offset += n as u64;
+
+            if frame.pop() != Some(0) {
+                return None; // Log was torn since opening, stop short
+            }
+            if !frame.is_empty() {
+                return Some(parse(offset, &frame));
+            }
+        }
+    }
+}
+
+/// Backward iterator over the records of a [`FrameLog`], created by
+/// [`FrameLog::iter_rev`].
+///
+/// Malformed or corrupted records are reported as [`io::ErrorKind::InvalidData`]
+/// errors wrapping the [`DecodeError`] or [`ChecksumError`](crate::crc::ChecksumError),
+/// after which iteration carries on.
+#[derive(Debug)]
+pub struct IterRev {
+    file: File,
+    start: u64,    // Position in the log of the first byte in the tail
+    tail: Vec<u8>, // Bytes read but not yet returned, without the delimiter
+}
+
+impl IterRev {
+    // Prepends the block before the tail, returning false at the start of the log
+    fn read_block(&mut self) -> io::Result<bool> {
+        if self.start == 0 {
+            return Ok(false);
+        }
+        let size = self.start.min(BLOCK_SIZE);
+        let mut block = vec![0u8; size as usize];
+        self.file.seek(SeekFrom::Start(self.start - size))?;
+        self.file.read_exact(&mut block)?;
+
+        block.extend_from_slice(&self.tail);
+        self.tail = block;
+        self.start -= size;
+        Ok(true)
+    }
+}
+
+impl Iterator for IterRev {
+    type Item = io::Result<LogRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // Split off the last frame if its start is already known
+            if let Some(pos) = self.tail.iter().rposition(|&b| b == 0) {
+                let frame = self.tail.split_off(pos + 1);
+                self.tail.pop();
+                if frame.is_empty() {
+                    continue;
+                }
+                return Some(parse(self.start + pos as u64 + 1, &frame));
+            }
+            match self.read_block() {
+                Ok(true) => continue,
+                Ok(false) if self.tail.is_empty() => return None,
+                Ok(false) => return Some(parse(0, &std::mem::take(&mut self.tail))),
+                Err(err) => {
+                    self.tail.clear();
+                    self.start = 0;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+// Decodes a frame of the log (without the delimiter) into a record
+fn parse(offset: u64, frame: &[u8]) -> io::Result<LogRecord> {
+    // The flags lead the payload, so they are the first data byte of the frame
+    // unless its first chunk is empty (malformed frames fail decoding anyway)
+    let flags = match frame {
+        [code, flags, ..] if *code > 1 => *flags,
+        _ => 0,
+    };
+    let mut decoded = vec![0u8; decode_buffer(frame.len())];
+    let len = if flags & FLAG_CHECKSUM != 0 {
+        decode_checked(frame, Crc32::CASTAGNOLI, &mut decoded).map_err(invalid)?
+    } else {
+        decode(frame, &mut decoded).map_err(invalid)?
+    };
+    if len == 0 {
+        return Err(invalid(DecodeError::EmptyInput));
+    }
+    let mut data = &decoded[1..len];
+    let mut timestamp = None;
+    if flags & FLAG_TIMESTAMP != 0 {
+        let Some((micros, rest)) = data.split_first_chunk::<8>() else {
+            return Err(invalid(DecodeError::EmptyInput));
+        };
+        timestamp = Some(UNIX_EPOCH + Duration::from_micros(u64::from_be_bytes(*micros)));
+        data = rest;
+    }
+    Ok(LogRecord {
+        offset,
+        timestamp,
+        data: data.to_vec(),
+    })
+}
+
+// Computes the length of the intact part of a log file: up to the end of the
+// last non-empty frame, provided it's delimited and parses correctly, or up to
+// its start otherwise
+fn recover(file: &mut File, size: u64) -> io::Result<u64> {
+    let Some(last) = rfind(file, size, |b| b != 0)? else {
+        return Ok(0);
+    };
+    let start = rfind(file, last, |b| b == 0)?.map_or(0, |pos| pos + 1);
+    if last + 1 == size {
+        return Ok(start);
+    }
+    let mut frame = vec![0u8; (last + 1 - start) as usize];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut frame)?;
+
+    match parse(start, &frame) {
+        Ok(_) => Ok(last + 2),
+        Err(_) => Ok(start),
+    }
+}
+
+// Finds the position of the last byte matching a predicate in the first `size`
+// bytes of a file
+fn rfind(file: &mut File, size: u64, pred: impl Fn(u8) -> bool) -> io::Result<Option<u64>> {
+    let mut end = size;
+    let mut block = vec![0u8; BLOCK_SIZE as usize];
+    while end > 0 {
+        let start = end.saturating_sub(BLOCK_SIZE);
+        let block = &mut block[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(block)?;
+        if let Some(pos) = block.iter().rposition(|&b| pred(b)) {
+            return Ok(Some(start + pos as u64));
+        }
+        end = start;
+    }
+    Ok(None)
+}
+
+// Wraps a decoding error into an I/O error
+fn invalid<E: Error + Send + Sync + 'static>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Returns a fresh path in the temporary directory
+    fn temp_path() -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("cobs-journal-{}-{n}.log", std::process::id()))
+    }
+
+    // Collects the data of all the records in a log, in both directions
+    fn contents(log: &FrameLog) -> Vec<Vec<u8>> {
+        let forward: Vec<_> = log.iter().unwrap().map(|r| r.unwrap()).collect();
+        let mut backward: Vec<_> = log.iter_rev().unwrap().map(|r| r.unwrap()).collect();
+        backward.reverse();
+        assert_eq!(forward, backward);
+        forward.into_iter().map(|r| r.data).collect()
+    }
+
+    #[test]
+    fn test_append_iterate() {
+        let path = temp_path();
+        let config = LogConfig {
+            checksum: true,
+            timestamps: true,
+            sync: SyncPolicy::Every(10),
+        };
+        let mut log = FrameLog::open(&path, config).unwrap();
+
+        // Records spanning many blocks and zero runs, with empty ones too
+        let records: Vec<Vec<u8>> = (0..300)
+            .map(|i| (0..i * 7 % 1000).map(|j| (i + j) as u8).collect())
+            .collect();
+        let before = SystemTime::now() - Duration::from_secs(1);
+        for record in &records {
+            log.append(record).unwrap();
+        }
+        assert_eq!(contents(&log), records);
+
+        let first = log.iter().unwrap().next().unwrap().unwrap();
+        assert_eq!(first.offset, 0);
+        assert!(first.timestamp.unwrap() > before);
+
+        // Reopening with another configuration reads the old records alike
+        drop(log);
+        let config = LogConfig {
+            checksum: false,
+            timestamps: false,
+            sync: SyncPolicy::Never,
+        };
+        let mut log = FrameLog::open(&path, config).unwrap();
+        let offset = log.append(b"plain").unwrap();
+        let last = log.iter_rev().unwrap().next().unwrap().unwrap();
+        assert_eq!((last.offset, last.timestamp), (offset, None));
+        assert_eq!(last.data, b"plain");
+        assert_eq!(log.iter().unwrap().count(), 301);
+
+        // Corrupting a record is caught by its checksum, without losing the rest
+        let mut bytes = fs::read(&path).unwrap();
+        let pos = bytes.iter().position(|&b| b == 0).unwrap() + 20;
+        bytes[pos] = bytes[pos].wrapping_add(1).max(1);
+        fs::write(&path, &bytes).unwrap();
+
+        let log = FrameLog::open(&path, config).unwrap();
+        let results: Vec<_> = log.iter().unwrap().collect();
+        assert_eq!(results.len(), 301);
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_failed_append_rollback() {
+        let path = temp_path();
+        let mut log = FrameLog::open(&path, LogConfig::default()).unwrap();
+        log.append(b"first").unwrap();
+
+        // A partially written frame is truncated away
+        let mut other = File::options().append(true).open(&path).unwrap();
+        other.write_all(&[0x05, 0x01]).unwrap();
+        log.rollback();
+        assert_eq!(fs::metadata(&path).unwrap().len(), log.len());
+        log.append(b"second").unwrap();
+        assert_eq!(contents(&log), [b"first".to_vec(), b"second".to_vec()]);
+
+        // A failed append that can't be rolled back poisons the log
+        let writable = std::mem::replace(&mut log.file, File::open(&path).unwrap());
+        assert!(log.append(b"third").is_err());
+        log.file = writable;
+        assert!(log.append(b"fourth").is_err());
+
+        let log = FrameLog::open(&path, LogConfig::default()).unwrap();
+        assert_eq!(contents(&log), [b"first".to_vec(), b"second".to_vec()]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_torn_tail_recovery() {
+        let path = temp_path();
+        let config = LogConfig {
+            checksum: true,
+            timestamps: false,
+            sync: SyncPolicy::Never,
+        };
+        let mut log = FrameLog::open(&path, config).unwrap();
+        let records: Vec<Vec<u8>> = vec![
+            b"first".to_vec(),
+            vec![0; 300],
+            vec![],
+            (0..=255).collect(),
+            b"last".to_vec(),
+        ];
+        let mut ends = Vec::new();
+        for record in &records {
+            log.append(record).unwrap();
+            ends.push(log.len());
+        }
+        drop(log);
+        let bytes = fs::read(&path).unwrap();
+
+        // Cut the log at every offset, all complete records must survive
+        for cut in 0..=bytes.len() {
+            fs::write(&path, &bytes[..cut]).unwrap();
+
+            let mut log = FrameLog::open(&path, config).unwrap();
+            let whole = ends.iter().filter(|&&end| end <= cut as u64).count();
+            let len = if whole == 0 { 0 } else { ends[whole - 1] };
+            assert_eq!(log.len(), len);
+            assert_eq!(log.truncated(), cut as u64 - len);
+            assert_eq!(fs::metadata(&path).unwrap().len(), len);
+            assert_eq!(contents(&log), records[..whole]);
+
+            // The log must stay appendable after the recovery
+            log.append(b"resumed").unwrap();
+            assert_eq!(contents(&log).last().unwrap(), b"resumed");
+
+            // Zero filled blocks after the cut must not pass for delimiters, only
+            // completing records that lacked nothing but theirs
+            let mut padded = bytes[..cut].to_vec();
+            padded.resize(cut + 16, 0);
+            fs::write(&path, &padded).unwrap();
+
+            let log = FrameLog::open(&path, config).unwrap();
+            let whole = ends.iter().filter(|&&end| end <= cut as u64 + 1).count();
+            let len = if whole == 0 { 0 } else { ends[whole - 1] };
+            assert_eq!(log.len(), len, "cut {cut}");
+            assert_eq!(log.truncated(), padded.len() as u64 - len);
+            assert_eq!(contents(&log), records[..whole]);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,6 +1,66 @@
 // cobs-rs: fast cobs encoder and decoder
 // Copyright 2025 Dark Bio AG. All rights reserved.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "std")]
+pub mod arq;
+mod buffer;
+mod chunks;
+#[cfg(feature = "tokio")]
+pub mod codec;
+pub mod compress;
+pub mod crc;
+#[cfg(feature = "std")]
+mod explain;
+pub mod fec;
+mod frame;
+#[cfg(feature = "std")]
+mod index;
+#[cfg(feature = "std")]
+pub mod io;
+mod iter;
+#[cfg(feature = "std")]
+pub mod journal;
+#[cfg(feature = "std")]
+pub mod logging;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "std")]
+pub mod mux;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod partial;
+#[cfg(feature = "std")]
+pub mod pcap;
+#[cfg(feature = "std")]
+pub mod rpc;
+#[cfg(feature = "aead")]
+pub mod seal;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod split;
+mod stuffer;
+#[cfg(test)]
+mod testutil;
+#[cfg(feature = "transfer")]
+pub mod transfer;
+
+pub use buffer::FrameBuffer;
+pub use chunks::{Chunk, Chunks, chunks};
+#[cfg(feature = "std")]
+pub use explain::{Explanation, Run, explain};
+pub use frame::{CobsFrame, ZeroFree};
+#[cfg(feature = "std")]
+pub use frame::{CobsFrameBuf, ZeroFreeBytes};
+#[cfg(feature = "std")]
+pub use index::{CobsIndex, decode_range};
+pub use iter::{DecodeIter, EncodeIter, decode_iter, encode_iter};
+#[cfg(feature = "rayon")]
+pub use parallel::{par_decode, par_decode_frames, par_encode};
+pub use partial::{EncodeCursor, encode_partial};
+pub use split::decode_split;
+
 /// Error types that can be returned from encoding.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
 pub enum EncodeError {
@@ -23,6 +83,19 @@ pub enum DecodeError {
     ChunkOverflow { at: usize, marker: u8, len: usize },
 }
 
+impl DecodeError {
+    /// Returns the position within the encoded input that the error points at,
+    /// if it points at a specific byte.
+    pub const fn position(&self) -> Option<usize> {
+        match *self {
+            DecodeError::ZeroMarker { at }
+            | DecodeError::ZeroBinary { at }
+            | DecodeError::ChunkOverflow { at, .. } => Some(at),
+            _ => None,
+        }
+    }
+}
+
 /// Computes the maximum size needed to COBS encode a blind input blob.
 #[inline]
 pub const fn encode_buffer(size: usize) -> usize {
@@ -65,13 +138,31 @@ pub fn encode(data: &[u8], encoded: &mut [u8]) -> Result<usize, EncodeError> {
 /// The caller must ensure `encoded` has at least `encode_buffer(data.len())` bytes.
 #[inline]
 pub fn encode_unsafe(data: &[u8], encoded: &mut [u8]) -> usize {
+    // Sanity check in debug builds that the user called it correctly
+    debug_assert!(encoded.len() >= encode_buffer(data.len()));
+    encode_into(data, encoded)
+}
+
+// Encodes a payload into a 0x00 delimited COBS frame at the start of a buffer
+// of at least encode_buffer(payload.len()) + 1 bytes. Returns the length of the
+// frame, delimiter included.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn encode_delimited(payload: &[u8], out: &mut [u8]) -> usize {
+    let len = encode_unsafe(payload, out);
+    out[len] = 0;
+    len + 1
+}
+
+// Encodes an opaque data blob with COBS into a buffer known to fit the exact
+// encoding, which may be smaller than what encode_buffer asks for.
+#[inline]
+pub(crate) fn encode_into(data: &[u8], encoded: &mut [u8]) -> usize {
     // The empty blob is always encoded as 0x01
     if data.is_empty() {
         encoded[0] = 0x01;
         return 1;
     }
-    // Sanity check in debug builds that the user called it correctly
-    debug_assert!(encoded.len() >= encode_buffer(data.len()));
 
     // Start pushing the bytes into the output array, skipping each marker byte
     // and backfilling it later
@@ -142,56 +233,29 @@ pub fn decode(data: &[u8], decoded: &mut [u8]) -> Result<usize, DecodeError> {
 /// The caller must ensure `decoded` has at least `decode_buffer(data.len())` bytes.
 #[inline]
 pub fn decode_unsafe(data: &[u8], decoded: &mut [u8]) -> Result<usize, DecodeError> {
-    // The empty blob is not a valid COBS encoding
-    if data.is_empty() {
-        return Err(DecodeError::EmptyInput);
-    }
-    // The empty text is always encoded as 0x01
-    if data.len() == 1 && data[0] == 0x01 {
-        return Ok(0);
-    }
     // Sanity check in debug builds that the user called it correctly
     debug_assert!(decoded.len() >= decode_buffer(data.len()));
 
-    // Consume the input stream one chunk at a time
-    unsafe {
-        let mut output_pos = 0usize;
-        let mut i = 0usize;
-
-        while i < data.len() {
-            // Zero cannot be part of a COBS encoded stream
-            let marker = *data.get_unchecked(i);
-            if marker == 0 {
-                return Err(DecodeError::ZeroMarker { at: i });
-            }
-            i += 1;
+    // Consume the input stream one validated chunk at a time
+    let mut output_pos = 0usize;
+    for chunk in chunks(data) {
+        let chunk = chunk?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                chunk.data.as_ptr(),
+                decoded.as_mut_ptr().add(output_pos),
+                chunk.data.len(),
+            );
+            output_pos += chunk.data.len();
 
-            // If the marker defines an overflowing chunk, abort
-            if i + (marker as usize) - 1 > data.len() {
-                return Err(DecodeError::ChunkOverflow {
-                    at: i - 1,
-                    marker,
-                    len: data.len(),
-                });
-            }
-            // Consume the entire chunk, ensuring there's no zero in it
-            for _ in 1..marker {
-                let b = *data.get_unchecked(i);
-                if b == 0 {
-                    return Err(DecodeError::ZeroBinary { at: i });
-                }
-                *decoded.get_unchecked_mut(output_pos) = b;
-                output_pos += 1;
-                i += 1;
-            }
             // If we had a partial chunk, there must be a zero following
-            if i < data.len() && marker != 0xff {
+            if chunk.implies_zero {
                 *decoded.get_unchecked_mut(output_pos) = 0;
                 output_pos += 1;
             }
         }
-        Ok(output_pos)
     }
+    Ok(output_pos)
 }
 
 #[cfg(test)]
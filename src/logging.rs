@@ -0,0 +1,733 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Binary log records in COBS frames, in the spirit of defmt.
+//!
+//! Each record (level, timestamp, target and fields) is packed into a compact
+//! binary form and written out as a 0x00 delimited frame into a [`Sink`]: a
+//! stream or an in-memory ring of the latest records. The `tracing` and `log`
+//! features provide a [`CobsLayer`] and a [`CobsLogger`] emitting such records,
+//! and [`Entries`] reads them back for pretty-printing.
+//!
+//! Records are laid out as the level byte, the timestamp in microseconds since
+//! the Unix epoch (8 bytes, big endian), the target, and the fields. Strings and
+//! counts are prefixed with their length as LEB128 varints, and field values are
+//! tagged with their type.
+
+use crate::io::FrameReader;
+use crate::{DecodeError, encode_buffer, encode_delimited};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Type tags of the field values
+const TAG_STR: u8 = 0;
+const TAG_I64: u8 = 1;
+const TAG_U64: u8 = 2;
+const TAG_F64: u8 = 3;
+const TAG_BOOL: u8 = 4;
+
+/// Error types that can be returned from reading log records.
+#[derive(Debug, thiserror::Error)]
+pub enum LogError {
+    #[error("truncated record")]
+    Truncated,
+    #[error("{0} trailing bytes after record")]
+    TrailingBytes(usize),
+    #[error("varint overflows 64 bits")]
+    VarintOverflow,
+    #[error("unknown level {0}")]
+    UnknownLevel(u8),
+    #[error("unknown value tag {0}")]
+    UnknownTag(u8),
+    #[error("invalid utf-8 in string")]
+    InvalidString,
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Severity of a log record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        })
+    }
+}
+
+/// Value of a field of a log record.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Bool(bool),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(v) => write!(f, "{v:?}"),
+            Value::I64(v) => write!(f, "{v}"),
+            Value::U64(v) => write!(f, "{v}"),
+            Value::F64(v) => write!(f, "{v}"),
+            Value::Bool(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+/// Log record, as carried in a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub level: Level,
+    pub timestamp: SystemTime,
+    pub target: String,
+    /// Fields of the record in order, the text of the record being the one
+    /// named "message" by convention.
+    pub fields: Vec<(String, Value)>,
+}
+
+impl LogEntry {
+    /// Returns the message of the record, if it has one.
+    pub fn message(&self) -> Option<&str> {
+        self.fields.iter().find_map(|(name, value)| match value {
+            Value::Str(text) if name == "message" => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Packs the record into its binary form, without framing it.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        let micros = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_micros() as u64);
+
+        out.push(self.level as u8);
+        out.extend_from_slice(&micros.to_be_bytes());
+        put_str(out, &self.target);
+        put_varint(out, self.fields.len() as u64);
+        for (name, value) in &self.fields {
+            put_str(out, name);
+            match value {
+                Value::Str(v) => {
+                    out.push(TAG_STR);
+                    put_str(out, v);
+                }
+                Value::I64(v) => {
+                    out.push(TAG_I64);
+                    put_varint(out, ((v << 1) ^ (v >> 63)) as u64);
+                }
+                Value::U64(v) => {
+                    out.push(TAG_U64);
+                    put_varint(out, *v);
+                }
+                Value::F64(v) => {
+                    out.push(TAG_F64);
+                    out.extend_from_slice(&v.to_be_bytes());
+                }
+                Value::Bool(v) => {
+                    out.push(TAG_BOOL);
+                    out.push(*v as u8);
+                }
+            }
+        }
+    }
+
+    /// Unpacks a record from its binary form (a decoded frame).
+    pub fn decode(data: &[u8]) -> Result<Self, LogError> {
+        let mut r = data;
+        let level = match take(&mut r, 1)?[0] {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            4 => Level::Debug,
+            5 => Level::Trace,
+            n => return Err(LogError::UnknownLevel(n)),
+        };
+        let micros = u64::from_be_bytes(take(&mut r, 8)?.try_into().unwrap());
+        let target = get_str(&mut r)?;
+
+        let count = get_varint(&mut r)?;
+        let mut fields = Vec::with_capacity(count.min(64) as usize);
+        for _ in 0..count {
+            let name = get_str(&mut r)?;
+            let value = match take(&mut r, 1)?[0] {
+                TAG_STR => Value::Str(get_str(&mut r)?),
+                TAG_I64 => {
+                    let v = get_varint(&mut r)?;
+                    Value::I64((v >> 1) as i64 ^ -((v & 1) as i64))
+                }
+                TAG_U64 => Value::U64(get_varint(&mut r)?),
+                TAG_F64 => Value::F64(f64::from_be_bytes(take(&mut r, 8)?.try_into().unwrap())),
+                TAG_BOOL => Value::Bool(take(&mut r, 1)?[0] != 0),
+                tag => return Err(LogError::UnknownTag(tag)),
+            };
+            fields.push((name, value));
+        }
+        if !r.is_empty() {
+            return Err(LogError::TrailingBytes(r.len()));
+        }
+        Ok(Self {
+            level,
+            timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+            target,
+            fields,
+        })
+    }
+
+    /// Packs the record and stuffs it into a 0x00 delimited COBS frame.
+    pub fn to_frame(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(64);
+        self.encode(&mut payload);
+
+        let mut frame = vec![0u8; encode_buffer(payload.len()) + 1];
+        let len = encode_delimited(&payload, &mut frame);
+        frame.truncate(len);
+        frame
+    }
+}
+
+impl fmt::Display for LogEntry {
+    /// Formats the record on a single line, as `seconds.micros LEVEL target:
+    /// message name=value...`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        write!(
+            f,
+            "{}.{:06} {:<5} {}:",
+            since.as_secs(),
+            since.subsec_micros(),
+            self.level,
+            self.target
+        )?;
+        if let Some(message) = self.message() {
+            write!(f, " {message}")?;
+        }
+        for (name, value) in &self.fields {
+            if name != "message" || !matches!(value, Value::Str(_)) {
+                write!(f, " {name}={value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Appends a LEB128 varint
+fn put_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+// Appends a length prefixed string
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_varint(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+// Splits off the next `n` bytes of the input
+fn take<'a>(r: &mut &'a [u8], n: usize) -> Result<&'a [u8], LogError> {
+    if r.len() < n {
+        return Err(LogError::Truncated);
+    }
+    let (head, tail) = r.split_at(n);
+    *r = tail;
+    Ok(head)
+}
+
+// Reads a LEB128 varint
+fn get_varint(r: &mut &[u8]) -> Result<u64, LogError> {
+    let mut v = 0u64;
+    let mut shift = 0;
+    loop {
+        let b = take(r, 1)?[0];
+        // The 10th byte only has room for the top bit, and must end the varint
+        if shift == 63 && b > 1 {
+            return Err(LogError::VarintOverflow);
+        }
+        v |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+        shift += 7;
+    }
+}
+
+// Reads a length prefixed string
+fn get_str(r: &mut &[u8]) -> Result<String, LogError> {
+    let len = get_varint(r)?;
+    let bytes = take(r, usize::try_from(len).map_err(|_| LogError::Truncated)?)?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| LogError::InvalidString)
+}
+
+/// Destination of framed log records. Logging must not fail, so sinks swallow
+/// their errors.
+pub trait Sink: Send + Sync + 'static {
+    /// Writes out a 0x00 delimited frame.
+    fn write_frame(&self, frame: &[u8]);
+
+    /// Flushes any buffered frames.
+    fn flush(&self) {}
+}
+
+impl<S: Sink> Sink for Arc<S> {
+    fn write_frame(&self, frame: &[u8]) {
+        (**self).write_frame(frame)
+    }
+
+    fn flush(&self) {
+        (**self).flush()
+    }
+}
+
+/// Sink writing frames into a stream, such as a file or a serial port.
+#[derive(Debug)]
+pub struct WriterSink<W: Write + Send + 'static> {
+    inner: Mutex<W>,
+}
+
+impl<W: Write + Send + 'static> WriterSink<W> {
+    /// Creates a sink on top of a byte stream.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    /// Unwraps the sink, returning the underlying stream.
+    pub fn into_inner(self) -> W {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<W: Write + Send + 'static> Sink for WriterSink<W> {
+    fn write_frame(&self, frame: &[u8]) {
+        if let Ok(mut inner) = self.inner.lock() {
+            let _ = inner.write_all(frame);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            let _ = inner.flush();
+        }
+    }
+}
+
+/// Sink keeping the latest frames in memory, up to a byte capacity, dropping
+/// the oldest ones to make room.
+#[derive(Debug)]
+pub struct RingBuffer {
+    capacity: usize,
+    state: Mutex<RingState>,
+}
+
+#[derive(Debug, Default)]
+struct RingState {
+    frames: VecDeque<Vec<u8>>,
+    size: usize,  // Total bytes of the frames held
+    dropped: u64, // Frames dropped since creation
+}
+
+impl RingBuffer {
+    /// Creates a ring holding up to `capacity` bytes of frames.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(RingState::default()),
+        }
+    }
+
+    /// Takes all the frames held, concatenated in the order they were written.
+    pub fn take(&self) -> Vec<u8> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.size = 0;
+        state.frames.drain(..).flatten().collect()
+    }
+
+    /// Returns the number of frames dropped to make room for newer ones.
+    pub fn dropped(&self) -> u64 {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.dropped
+    }
+}
+
+impl Sink for RingBuffer {
+    fn write_frame(&self, frame: &[u8]) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if frame.len() > self.capacity {
+            state.dropped += 1;
+            return;
+        }
+        while state.size + frame.len() > self.capacity {
+            let old = state.frames.pop_front().expect("size accounts for frames");
+            state.size -= old.len();
+            state.dropped += 1;
+        }
+        state.size += frame.len();
+        state.frames.push_back(frame.to_vec());
+    }
+}
+
+/// Iterator reading log records from a stream of frames. Corrupt frames are
+/// yielded as errors and skipped, but the iteration ends after the first I/O
+/// error, such as a torn trailing frame.
+pub struct Entries<R: Read> {
+    reader: FrameReader<R>,
+    failed: bool, // Whether the stream returned an I/O error
+}
+
+impl<R: Read> Entries<R> {
+    /// Creates a record reader on top of a byte stream.
+    pub fn new(inner: R) -> Self {
+        Self {
+            reader: FrameReader::new(inner),
+            failed: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for Entries<R> {
+    type Item = Result<LogEntry, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.reader.read_frame() {
+            Ok(Some(frame)) => Some(LogEntry::decode(&frame)),
+            Ok(None) => None,
+            Err(err) => match err.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()) {
+                Some(&err) => Some(Err(LogError::Decode(err))),
+                None => {
+                    self.failed = true;
+                    Some(Err(LogError::Io(err)))
+                }
+            },
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub use self::tracing_layer::CobsLayer;
+
+#[cfg(feature = "tracing")]
+mod tracing_layer {
+    use super::*;
+    use tracing::field::{Field, Visit};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer};
+
+    impl From<&tracing::Level> for Level {
+        fn from(level: &tracing::Level) -> Self {
+            match *level {
+                tracing::Level::ERROR => Level::Error,
+                tracing::Level::WARN => Level::Warn,
+                tracing::Level::INFO => Level::Info,
+                tracing::Level::DEBUG => Level::Debug,
+                tracing::Level::TRACE => Level::Trace,
+            }
+        }
+    }
+
+    /// Tracing layer writing every event as a framed log record into a sink.
+    /// Spans are not recorded.
+    #[derive(Debug)]
+    pub struct CobsLayer<S: Sink> {
+        sink: S,
+    }
+
+    impl<S: Sink> CobsLayer<S> {
+        /// Creates a layer emitting into a sink.
+        pub fn new(sink: S) -> Self {
+            Self { sink }
+        }
+    }
+
+    impl<S: Sink, Sub: Subscriber> Layer<Sub> for CobsLayer<S> {
+        fn on_event(&self, event: &Event<'_>, _: Context<'_, Sub>) {
+            let meta = event.metadata();
+            let mut fields = Fields(Vec::new());
+            event.record(&mut fields);
+
+            let entry = LogEntry {
+                level: meta.level().into(),
+                timestamp: SystemTime::now(),
+                target: meta.target().to_string(),
+                fields: fields.0,
+            };
+            self.sink.write_frame(&entry.to_frame());
+        }
+    }
+
+    // Visitor collecting the fields of an event
+    struct Fields(Vec<(String, Value)>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .push((field.name().to_string(), Value::Str(format!("{value:?}"))));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0
+                .push((field.name().to_string(), Value::Str(value.to_string())));
+        }
+
+        fn record_i64(&mut self, field: &Field, value: i64) {
+            self.0.push((field.name().to_string(), Value::I64(value)));
+        }
+
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.push((field.name().to_string(), Value::U64(value)));
+        }
+
+        fn record_f64(&mut self, field: &Field, value: f64) {
+            self.0.push((field.name().to_string(), Value::F64(value)));
+        }
+
+        fn record_bool(&mut self, field: &Field, value: bool) {
+            self.0.push((field.name().to_string(), Value::Bool(value)));
+        }
+    }
+}
+
+#[cfg(feature = "log")]
+pub use self::log_backend::CobsLogger;
+
+#[cfg(feature = "log")]
+mod log_backend {
+    use super::*;
+
+    impl From<log::Level> for Level {
+        fn from(level: log::Level) -> Self {
+            match level {
+                log::Level::Error => Level::Error,
+                log::Level::Warn => Level::Warn,
+                log::Level::Info => Level::Info,
+                log::Level::Debug => Level::Debug,
+                log::Level::Trace => Level::Trace,
+            }
+        }
+    }
+
+    /// Logger writing every record as a framed log record into a sink.
+    #[derive(Debug)]
+    pub struct CobsLogger<S: Sink> {
+        sink: S,
+        level: log::LevelFilter,
+    }
+
+    impl<S: Sink> CobsLogger<S> {
+        /// Creates a logger emitting records up to a level into a sink.
+        pub fn new(sink: S, level: log::LevelFilter) -> Self {
+            Self { sink, level }
+        }
+
+        /// Installs the logger as the global one.
+        pub fn init(self) -> Result<(), log::SetLoggerError> {
+            log::set_max_level(self.level);
+            log::set_boxed_logger(Box::new(self))
+        }
+    }
+
+    impl<S: Sink> log::Log for CobsLogger<S> {
+        fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+            metadata.level() <= self.level
+        }
+
+        fn log(&self, record: &log::Record<'_>) {
+            if !self.enabled(record.metadata()) {
+                return;
+            }
+            let entry = LogEntry {
+                level: record.level().into(),
+                timestamp: SystemTime::now(),
+                target: record.target().to_string(),
+                fields: vec![("message".into(), Value::Str(record.args().to_string()))],
+            };
+            self.sink.write_frame(&entry.to_frame());
+        }
+
+        fn flush(&self) {
+            self.sink.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Creates a record with a field of every type
+    fn entry() -> LogEntry {
+        LogEntry {
+            level: Level::Warn,
+            timestamp: UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456),
+            target: "dev::sensor".into(),
+            fields: vec![
+                ("message".into(), Value::Str("over\0heat".into())),
+                ("temp".into(), Value::F64(81.5)),
+                ("delta".into(), Value::I64(-300)),
+                ("count".into(), Value::U64(u64::MAX)),
+                ("latched".into(), Value::Bool(true)),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_entry_roundtrip() {
+        let entry = entry();
+        let mut payload = Vec::new();
+        entry.encode(&mut payload);
+        assert_eq!(LogEntry::decode(&payload).unwrap(), entry);
+
+        for len in 0..payload.len() {
+            assert!(LogEntry::decode(&payload[..len]).is_err());
+        }
+        payload.extend_from_slice(b"\x00\x00");
+        assert!(matches!(
+            LogEntry::decode(&payload),
+            Err(LogError::TrailingBytes(2))
+        ));
+        assert_eq!(
+            entry.to_string(),
+            "1700000000.123456 WARN  dev::sensor: over\0heat temp=81.5 delta=-300 count=18446744073709551615 latched=true"
+        );
+    }
+
+    #[test]
+    fn test_varint_bounds() {
+        let mut max = Vec::new();
+        put_varint(&mut max, u64::MAX);
+        assert_eq!(max.len(), 10);
+        assert_eq!(get_varint(&mut &max[..]).unwrap(), u64::MAX);
+
+        // Bits beyond the 64th, or an 11th byte, must be rejected
+        let mut over = max.clone();
+        over[9] = 0x02;
+        assert!(matches!(
+            get_varint(&mut &over[..]),
+            Err(LogError::VarintOverflow)
+        ));
+        over[9] = 0x81;
+        over.push(0x00);
+        assert!(matches!(
+            get_varint(&mut &over[..]),
+            Err(LogError::VarintOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let frame = entry().to_frame();
+        let ring = RingBuffer::new(frame.len() * 3 + 1);
+        for _ in 0..5 {
+            ring.write_frame(&frame);
+        }
+        assert_eq!(ring.dropped(), 2);
+
+        let mut data = ring.take();
+        data.extend_from_slice(&[0x05, 0x01, 0x00]);
+        let entries: Vec<_> = Entries::new(&data[..]).collect();
+        assert_eq!(entries.len(), 4);
+        assert!(entries[..3].iter().all(|e| e.as_ref().unwrap() == &entry()));
+        assert!(matches!(entries[3], Err(LogError::Decode(_))));
+        assert!(ring.take().is_empty());
+    }
+
+    #[test]
+    fn test_entries_stop_on_io_error() {
+        let frame = entry().to_frame();
+        let mut data = frame.repeat(2);
+        data.extend_from_slice(&frame[..frame.len() / 2]);
+        let entries: Vec<_> = Entries::new(&data[..]).collect();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[..2].iter().all(|e| e.as_ref().unwrap() == &entry()));
+        assert!(matches!(entries[2], Err(LogError::Io(_))));
+
+        // A stream failing on every read must not be retried forever
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("broken"))
+            }
+        }
+        let entries: Vec<_> = Entries::new(Broken).collect();
+        assert!(matches!(entries[..], [Err(LogError::Io(_))]));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_layer() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let ring = Arc::new(RingBuffer::new(4096));
+        let subscriber = tracing_subscriber::registry().with(CobsLayer::new(ring.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "app", port = 7u64, up = true, "link {}", "ready");
+            tracing::error!(code = -5i64, "failed");
+        });
+        let data = ring.take();
+        let entries: Vec<LogEntry> = Entries::new(&data[..]).map(Result::unwrap).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            (entries[0].level, entries[0].target.as_str()),
+            (Level::Info, "app")
+        );
+        assert_eq!(entries[0].message(), Some("link ready"));
+        assert_eq!(entries[0].fields[1], ("port".into(), Value::U64(7)));
+        assert_eq!(entries[0].fields[2], ("up".into(), Value::Bool(true)));
+        assert_eq!(entries[1].fields[1], ("code".into(), Value::I64(-5)));
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_log_backend() {
+        use log::Log;
+
+        let sink = Arc::new(WriterSink::new(Vec::new()));
+        let logger = CobsLogger::new(sink.clone(), log::LevelFilter::Info);
+        for level in [log::Level::Warn, log::Level::Debug] {
+            logger.log(
+                &log::Record::builder()
+                    .level(level)
+                    .target("boot")
+                    .args(format_args!("stage {}", 2))
+                    .build(),
+            );
+        }
+        drop(logger);
+        let data = Arc::into_inner(sink).unwrap().into_inner();
+        let entries: Vec<LogEntry> = Entries::new(&data[..]).map(Result::unwrap).collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].level, Level::Warn);
+        assert_eq!(entries[0].message(), Some("stage 2"));
+    }
+}
@@ -0,0 +1,239 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Zero-copy access to the frames of large capture files, memory mapped.
+//!
+//! Frames are handed out as slices of the mapped file in their encoded form and
+//! only decoded on request, so skimming through a capture costs little more than
+//! scanning it for delimiters. With the `rayon` feature, captures can also be
+//! decoded across the thread pool, split between frames.
+
+use crate::{DecodeError, decode, decode_buffer};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+#[cfg(feature = "rayon")]
+use {crate::parallel::frame_splits, rayon::prelude::*};
+
+/// Capture file of 0x00 delimited COBS frames, mapped into memory.
+#[derive(Debug)]
+pub struct MmapFrames {
+    map: Mmap,
+}
+
+impl MmapFrames {
+    /// Maps a capture file into memory.
+    ///
+    /// The file must not be modified while mapped: the mapping reflects changes
+    /// made by other processes, and truncating the file under it crashes the
+    /// process on access. Appending to it is harmless, but the new frames are
+    /// not seen.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+
+        // SAFETY: the caller is documented to not modify the file while mapped
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self { map })
+    }
+
+    /// Returns the raw contents of the capture.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Iterates over the frames of the capture, in order.
+    pub fn iter(&self) -> Frames<'_> {
+        frames(&self.map)
+    }
+
+    /// Applies a function to every frame of the capture across the rayon thread
+    /// pool, returning the results in the order of the frames. The capture is
+    /// split into pieces right after delimiters, so no frame is cut apart.
+    #[cfg(feature = "rayon")]
+    pub fn par_map<T, F>(&self, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(Frame<'_>) -> T + Sync,
+    {
+        let data = self.as_bytes();
+        let pieces: Vec<Vec<T>> = frame_splits(data)
+            .par_windows(2)
+            .map(|w| {
+                Frames {
+                    data: &data[..w[1]],
+                    pos: w[0],
+                }
+                .map(&f)
+                .collect()
+            })
+            .collect();
+        pieces.into_iter().flatten().collect()
+    }
+
+    /// Decodes every frame of the capture across the rayon thread pool,
+    /// returning the frame offsets along with the results, in order.
+    #[cfg(feature = "rayon")]
+    pub fn par_decode(&self) -> Vec<(usize, Result<Vec<u8>, DecodeError>)> {
+        self.par_map(|frame| (frame.offset, frame.decode()))
+    }
+}
+
+impl<'a> IntoIterator for &'a MmapFrames {
+    type Item = Frame<'a>;
+    type IntoIter = Frames<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Frame of a capture in its encoded form, without the delimiter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    /// Position of the frame within the capture.
+    pub offset: usize,
+    /// Encoded bytes of the frame.
+    pub encoded: &'a [u8],
+    /// Whether the frame is terminated by a delimiter, which only the trailing
+    /// frame of a cut off capture is not.
+    pub delimited: bool,
+}
+
+impl Frame<'_> {
+    /// Decodes the frame into a freshly allocated payload.
+    pub fn decode(&self) -> Result<Vec<u8>, DecodeError> {
+        let mut decoded = vec![0u8; decode_buffer(self.encoded.len())];
+        let len = decode(self.encoded, &mut decoded)?;
+        decoded.truncate(len);
+        Ok(decoded)
+    }
+
+    /// Decodes the frame into a caller provided buffer, returning the length of
+    /// the payload.
+    pub fn decode_into(&self, decoded: &mut [u8]) -> Result<usize, DecodeError> {
+        decode(self.encoded, decoded)
+    }
+}
+
+/// Iterator over the frames of a buffer of 0x00 delimited COBS frames, created
+/// by [`frames`] or [`MmapFrames::iter`]. Empty frames (consecutive delimiters)
+/// are skipped.
+#[derive(Debug, Clone)]
+pub struct Frames<'a> {
+    data: &'a [u8],
+    pos: usize, // Position of the next frame in the data
+}
+
+/// Iterates over the frames of an in-memory buffer of 0x00 delimited COBS
+/// frames, the same way [`MmapFrames`] does over a file.
+pub fn frames(data: &[u8]) -> Frames<'_> {
+    Frames { data, pos: 0 }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Frame<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.data.len() {
+            let offset = self.pos;
+            let rest = &self.data[offset..];
+            let (len, delimited) = match rest.iter().position(|&b| b == 0) {
+                Some(i) => (i, true),
+                None => (rest.len(), false),
+            };
+            self.pos += len + delimited as usize;
+            if len > 0 {
+                return Some(Frame {
+                    offset,
+                    encoded: &rest[..len],
+                    delimited,
+                });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode, encode_buffer};
+    use rand::Rng;
+    use std::fs;
+
+    // Offsets and decoding results of the frames of a capture
+    type Decoded = Vec<(usize, Result<Vec<u8>, DecodeError>)>;
+
+    // Builds a capture of random frames, a few of them corrupt, returning the
+    // capture along with the offset and expected decoding of each frame
+    fn capture(count: usize) -> (Vec<u8>, Decoded) {
+        let mut rng = rand::rng();
+        let mut data = vec![0, 0];
+        let mut want = Vec::new();
+        for i in 0..count {
+            let payload: Vec<u8> = (0..rng.random_range(0..600))
+                .map(|_| rng.random_range(0..4))
+                .collect();
+            let mut frame = vec![0u8; encode_buffer(payload.len())];
+            let len = encode(&payload, &mut frame).unwrap();
+            frame.truncate(len);
+
+            let result = if i % 97 == 5 {
+                frame[0] = frame[0].saturating_add(200);
+                let mut buf = vec![0u8; decode_buffer(frame.len())];
+                decode(&frame, &mut buf).map(|len| {
+                    buf.truncate(len);
+                    buf
+                })
+            } else {
+                Ok(payload)
+            };
+            want.push((data.len(), result));
+            data.extend_from_slice(&frame);
+            data.push(0);
+        }
+        (data, want)
+    }
+
+    #[test]
+    fn test_mmap_iterate() {
+        let (data, want) = capture(500);
+        let path = std::env::temp_dir().join(format!("cobs-mmap-{}.bin", std::process::id()));
+        fs::write(&path, &data).unwrap();
+
+        let frames = MmapFrames::open(&path).unwrap();
+        assert_eq!(frames.as_bytes(), &data[..]);
+
+        let have: Vec<_> = frames.iter().map(|f| (f.offset, f.decode())).collect();
+        assert_eq!(have, want);
+        assert!(frames.iter().all(|f| f.delimited));
+        drop(frames);
+        fs::remove_file(&path).unwrap();
+
+        // Cut off captures end in an undelimited frame
+        let last = frames_of(&data[..data.len() - 3]).pop().unwrap();
+        assert_eq!(last.offset, want[want.len() - 1].0);
+        assert!(!last.delimited);
+    }
+
+    // Collects the frames of an in-memory capture
+    fn frames_of(data: &[u8]) -> Vec<Frame<'_>> {
+        frames(data).collect()
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_mmap_par_decode() {
+        let (data, want) = capture(5000);
+        let path = std::env::temp_dir().join(format!("cobs-mmap-par-{}.bin", std::process::id()));
+        fs::write(&path, &data).unwrap();
+
+        let frames = MmapFrames::open(&path).unwrap();
+        assert!(data.len() > 4 * crate::parallel::MIN_PIECE_SIZE);
+        assert_eq!(frames.par_decode(), want);
+        drop(frames);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,327 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Channel multiplexing over a single COBS link.
+//!
+//! Every frame is tagged with a leading channel byte. The [`Mux`] queues outgoing
+//! payloads and releases them by channel priority, while the [`Demux`] validates
+//! incoming frames and routes them to per-channel queues or handlers.
+//!
+//! Both halves are transport agnostic: they produce and consume frame payloads,
+//! which can be carried by the blocking [`io`](crate::io) adapters (helpers are
+//! provided) or by the async [`CobsCodec`](crate::codec::CobsCodec).
+
+use crate::io::{FrameReader, FrameWriter};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::io::{self, Read, Write};
+
+/// Error types that can be returned from multiplexing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum MuxError {
+    #[error("empty frame")]
+    EmptyFrame,
+    #[error("unknown channel {0}")]
+    UnknownChannel(u8),
+    #[error("payload too large on channel {channel}: have {size} bytes, max {max} bytes")]
+    TooLarge {
+        channel: u8,
+        size: usize,
+        max: usize,
+    },
+}
+
+/// Outgoing tagged frame waiting in the queue, ordered by priority and sequence
+/// number only.
+#[derive(Debug)]
+struct Queued {
+    priority: u8,
+    seq: u64,
+    frame: Vec<u8>,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        (self.priority, self.seq) == (other.priority, other.seq)
+    }
+}
+
+impl Eq for Queued {}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Higher priority first, then first in first out
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Sending half of a multiplexed link.
+#[derive(Debug, Default)]
+pub struct Mux {
+    channels: HashMap<u8, (usize, u8)>, // Maximum payload size and priority
+    queue: BinaryHeap<Queued>,
+    seq: u64,
+}
+
+impl Mux {
+    /// Creates a multiplexer without any channels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers (or reconfigures) a channel with a maximum payload size and a
+    /// priority. Frames of higher priority channels are released first.
+    pub fn add_channel(&mut self, channel: u8, max_size: usize, priority: u8) {
+        self.channels.insert(channel, (max_size, priority));
+    }
+
+    /// Queues a payload for sending on a channel.
+    pub fn send(&mut self, channel: u8, payload: &[u8]) -> Result<(), MuxError> {
+        let &(max, priority) = self
+            .channels
+            .get(&channel)
+            .ok_or(MuxError::UnknownChannel(channel))?;
+        if payload.len() > max {
+            return Err(MuxError::TooLarge {
+                channel,
+                size: payload.len(),
+                max,
+            });
+        }
+        let mut frame = Vec::with_capacity(payload.len() + 1);
+        frame.push(channel);
+        frame.extend_from_slice(payload);
+
+        self.queue.push(Queued {
+            priority,
+            seq: self.seq,
+            frame,
+        });
+        self.seq += 1;
+        Ok(())
+    }
+
+    /// Returns the next tagged frame payload to transmit, highest priority first.
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        self.queue.pop().map(|q| q.frame)
+    }
+
+    /// Returns the number of frames waiting to be transmitted.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns whether there are no frames waiting to be transmitted.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Writes all the queued frames into a frame writer, highest priority first.
+    /// Returns the number of frames written.
+    pub fn write_to<W: Write>(&mut self, writer: &mut FrameWriter<W>) -> io::Result<usize> {
+        let mut written = 0;
+        while let Some(frame) = self.queue.peek() {
+            writer.write_frame(&frame.frame)?;
+            self.queue.pop();
+            written += 1;
+        }
+        Ok(written)
+    }
+}
+
+/// Callback invoked with the payloads arriving on a channel.
+type Handler = Box<dyn FnMut(&[u8]) + Send>;
+
+/// Destination of the frames arriving on a channel.
+enum Route {
+    Queue(VecDeque<Vec<u8>>),
+    Handler(Handler),
+}
+
+/// Receiving half of a multiplexed link.
+#[derive(Default)]
+pub struct Demux {
+    channels: HashMap<u8, (usize, Route)>,
+}
+
+impl Demux {
+    /// Creates a demultiplexer without any channels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a channel whose frames are queued up, to be retrieved via
+    /// [`recv`](Self::recv).
+    pub fn add_queue(&mut self, channel: u8, max_size: usize) {
+        self.channels
+            .insert(channel, (max_size, Route::Queue(VecDeque::new())));
+    }
+
+    /// Registers a channel whose frames are passed to a handler as they arrive.
+    pub fn add_handler<F>(&mut self, channel: u8, max_size: usize, handler: F)
+    where
+        F: FnMut(&[u8]) + Send + 'static,
+    {
+        self.channels
+            .insert(channel, (max_size, Route::Handler(Box::new(handler))));
+    }
+
+    /// Routes a decoded frame payload to its channel. Returns the channel it was
+    /// routed to.
+    pub fn route(&mut self, frame: &[u8]) -> Result<u8, MuxError> {
+        let (&channel, payload) = frame.split_first().ok_or(MuxError::EmptyFrame)?;
+        let (max, route) = self
+            .channels
+            .get_mut(&channel)
+            .ok_or(MuxError::UnknownChannel(channel))?;
+        if payload.len() > *max {
+            return Err(MuxError::TooLarge {
+                channel,
+                size: payload.len(),
+                max: *max,
+            });
+        }
+        match route {
+            Route::Queue(queue) => queue.push_back(payload.to_vec()),
+            Route::Handler(handler) => handler(payload),
+        }
+        Ok(channel)
+    }
+
+    /// Retrieves the oldest payload queued on a channel.
+    pub fn recv(&mut self, channel: u8) -> Option<Vec<u8>> {
+        match self.channels.get_mut(&channel) {
+            Some((_, Route::Queue(queue))) => queue.pop_front(),
+            _ => None,
+        }
+    }
+
+    /// Reads the next frame from a frame reader and routes it. Returns the channel
+    /// it was routed to, or `None` if the stream ended. Frames that can't be
+    /// routed are reported as [`io::ErrorKind::InvalidData`] errors.
+    pub fn read_from<R: Read>(&mut self, reader: &mut FrameReader<R>) -> io::Result<Option<u8>> {
+        match reader.read_frame()? {
+            Some(frame) => self
+                .route(&frame)
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_mux_priorities_and_limits() {
+        let mut mux = Mux::new();
+        mux.add_channel(0, 64, 0);
+        mux.add_channel(1, 8, 9);
+
+        mux.send(0, b"log line 1").unwrap();
+        mux.send(1, b"stop").unwrap();
+        mux.send(0, b"log line 2").unwrap();
+        mux.send(1, b"go").unwrap();
+
+        assert_eq!(mux.send(2, b"?"), Err(MuxError::UnknownChannel(2)));
+        assert_eq!(
+            mux.send(1, b"too long for control"),
+            Err(MuxError::TooLarge {
+                channel: 1,
+                size: 20,
+                max: 8
+            })
+        );
+        let order: Vec<Vec<u8>> = std::iter::from_fn(|| mux.pop()).collect();
+        assert_eq!(
+            order,
+            [
+                &b"\x01stop"[..],
+                b"\x01go",
+                b"\x00log line 1",
+                b"\x00log line 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mux_over_blocking_io() {
+        let mut mux = Mux::new();
+        mux.add_channel(0, 1024, 0);
+        mux.add_channel(1, 1024, 1);
+        mux.add_channel(7, 1024, 0);
+
+        mux.send(0, b"console").unwrap();
+        mux.send(1, &[0, 1, 0, 2]).unwrap();
+        mux.send(7, b"unrouted").unwrap();
+
+        let mut writer = FrameWriter::new(Vec::new());
+        assert_eq!(mux.write_to(&mut writer).unwrap(), 3);
+        let stream = writer.into_inner();
+
+        let telemetry = Arc::new(Mutex::new(Vec::new()));
+        let sink = telemetry.clone();
+
+        let mut demux = Demux::new();
+        demux.add_queue(0, 1024);
+        demux.add_handler(1, 1024, move |p| sink.lock().unwrap().push(p.to_vec()));
+
+        let mut reader = FrameReader::new(&stream[..]);
+        assert_eq!(demux.read_from(&mut reader).unwrap(), Some(1));
+        assert_eq!(demux.read_from(&mut reader).unwrap(), Some(0));
+        assert_eq!(
+            demux.read_from(&mut reader).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(demux.read_from(&mut reader).unwrap(), None);
+
+        assert_eq!(demux.recv(0).unwrap(), b"console");
+        assert_eq!(*telemetry.lock().unwrap(), [vec![0, 1, 0, 2]]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_mux_over_async_codec() {
+        use crate::codec::CobsCodec;
+        use futures::{SinkExt, StreamExt};
+        use tokio_util::codec::{FramedRead, FramedWrite};
+
+        let mut mux = Mux::new();
+        mux.add_channel(3, 1024, 0);
+        mux.add_channel(4, 1024, 0);
+        for i in 0..20u8 {
+            mux.send(3 + i % 2, &[i; 100]).unwrap();
+        }
+        let (client, server) = tokio::io::duplex(64);
+        let sender = tokio::spawn(async move {
+            let mut writer = FramedWrite::new(client, CobsCodec::new());
+            while let Some(frame) = mux.pop() {
+                writer.send(frame).await.unwrap();
+            }
+        });
+        let mut demux = Demux::new();
+        demux.add_queue(3, 1024);
+        demux.add_queue(4, 1024);
+
+        let mut reader = FramedRead::new(server, CobsCodec::new());
+        while let Some(frame) = reader.next().await {
+            demux.route(&frame.unwrap().unwrap()).unwrap();
+        }
+        sender.await.unwrap();
+
+        for i in 0..20u8 {
+            assert_eq!(demux.recv(3 + i % 2).unwrap(), [i; 100]);
+        }
+    }
+}
@@ -0,0 +1,371 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+use crate::index::walk;
+use crate::{
+    DecodeError, EncodeError, decode_buffer, decode_unsafe, encode_buffer, encode_into,
+    encode_unsafe,
+};
+use rayon::prelude::*;
+
+/// Inputs smaller than this are not worth splitting across threads.
+pub(crate) const MIN_PIECE_SIZE: usize = 64 * 1024;
+
+// Computes the size of the pieces to split an input of the given length into,
+// aiming for a few pieces per thread to smooth out imbalances
+pub(crate) fn piece_size(len: usize) -> usize {
+    len.div_ceil(rayon::current_num_threads() * 4)
+        .max(MIN_PIECE_SIZE)
+}
+
+// Computes the boundaries of the pieces to split a buffer of 0x00 delimited
+// frames into, each ending right after a delimiter (or at the end of the data),
+// so that no frame is cut apart
+pub(crate) fn frame_splits(data: &[u8]) -> Vec<usize> {
+    let size = piece_size(data.len());
+
+    let mut splits = vec![0];
+    let mut start = 0;
+    while data.len() - start > size {
+        match data[start + size..].iter().position(|&b| b == 0) {
+            Some(i) => {
+                start += size + i + 1;
+                splits.push(start);
+            }
+            None => break,
+        }
+    }
+    if *splits.last().unwrap() < data.len() {
+        splits.push(data.len());
+    }
+    splits
+}
+
+/// Encodes an opaque data blob with COBS using 0 as the sentinel value, split
+/// across the rayon thread pool. Returns the number of bytes the encoding took.
+/// Returns an error if the output buffer is too small. The output is identical
+/// to [`encode`](crate::encode).
+pub fn par_encode(data: &[u8], encoded: &mut [u8]) -> Result<usize, EncodeError> {
+    let want = encode_buffer(data.len());
+    if encoded.len() < want {
+        return Err(EncodeError::BufferTooSmall {
+            have: encoded.len(),
+            want,
+        });
+    }
+    // Split the input at points where the encoder state resets: right after a
+    // zero byte, or at the end of a full 254 byte block
+    let size = piece_size(data.len());
+
+    let mut splits = vec![0];
+    let mut start = 0;
+    while data.len() - start > size {
+        match split_point(data, start, start + size) {
+            Some(split) => {
+                splits.push(split);
+                start = split;
+            }
+            None => break,
+        }
+    }
+    splits.push(data.len());
+
+    if splits.len() == 2 {
+        return Ok(encode_unsafe(data, encoded));
+    }
+    // Pieces terminated by a zero end in an empty trailing chunk that the next
+    // piece's first chunk stands in for, so leave it out of their encodings
+    let pieces: Vec<(&[u8], bool)> = splits
+        .windows(2)
+        .map(|w| {
+            let piece = &data[w[0]..w[1]];
+            (piece, w[1] < data.len() && piece[piece.len() - 1] == 0)
+        })
+        .collect();
+    let lens: Vec<usize> = pieces
+        .par_iter()
+        .map(|&(piece, open)| encoded_len(piece) - open as usize)
+        .collect();
+
+    // Carve the output into the exact regions of each piece and encode them
+    // straight into place
+    let mut jobs = Vec::with_capacity(pieces.len());
+    let mut rest = &mut encoded[..];
+    for (&piece, &len) in pieces.iter().zip(&lens) {
+        let (head, tail) = rest.split_at_mut(len);
+        jobs.push((piece, head));
+        rest = tail;
+    }
+    jobs.into_par_iter().for_each(|((piece, open), out)| {
+        if !open {
+            encode_into(piece, out);
+            return;
+        }
+        // Encode without the terminating zero, which only differs in leaving an
+        // empty chunk after a full block unopened; open it by hand in that case
+        let len = encode_into(&piece[..piece.len() - 1], out);
+        if len < out.len() {
+            out[len] = 0x01;
+        }
+    });
+    Ok(lens.iter().sum())
+}
+
+// Computes the exact length of the encoding of a blob: a code byte leading the
+// data and standing in for each zero, plus one for each full block of non-zero
+// bytes, unless such a block ends the data.
+fn encoded_len(data: &[u8]) -> usize {
+    let mut len = data.len() + 1;
+    let mut last = 0;
+    for run in data.split(|&b| b == 0) {
+        len += run.len() / 254;
+        last = run.len();
+    }
+    if last > 0 && last % 254 == 0 {
+        len -= 1;
+    }
+    len
+}
+
+// Finds a position at or after `target` where the encoder state resets, given
+// that it was reset at `start`. Returns `None` if there's no such position before
+// the end of the data.
+fn split_point(data: &[u8], start: usize, target: usize) -> Option<usize> {
+    // If a zero is coming up within a block's length, split right after it
+    let window = &data[target..(target + 254).min(data.len())];
+    if let Some(i) = window.iter().position(|&b| b == 0) {
+        let split = target + i + 1;
+        return (split < data.len()).then_some(split);
+    }
+    // Otherwise the target is within a long run of non-zero bytes, find where
+    // that run started and split at the next block boundary
+    let run = data[start..target]
+        .iter()
+        .rposition(|&b| b == 0)
+        .map_or(start, |i| start + i + 1);
+
+    let split = run + (target - run).div_ceil(254) * 254;
+    (split < data.len()).then_some(split)
+}
+
+/// Decodes an opaque data blob with COBS using 0 as the sentinel value, split
+/// across the rayon thread pool. Returns the number of bytes the decoding took.
+/// Returns an error if the output buffer is too small or if the input is
+/// malformed. The output and errors are identical to [`decode`](crate::decode).
+pub fn par_decode(data: &[u8], decoded: &mut [u8]) -> Result<usize, DecodeError> {
+    if data.is_empty() {
+        return Err(DecodeError::EmptyInput);
+    }
+    if data.len() > 1 {
+        let want = decode_buffer(data.len());
+        if decoded.len() < want {
+            return Err(DecodeError::BufferTooSmall {
+                have: decoded.len(),
+                want,
+            });
+        }
+    }
+    let size = piece_size(data.len());
+    if data.len() <= size {
+        return decode_unsafe(data, decoded);
+    }
+    // Follow the chain of code bytes to find where each chunk lands, then group
+    // the chunks into similarly sized pieces
+    let (chunks, decoded_len, chain_err) = walk(data);
+
+    let mut pieces = Vec::new();
+    let mut first = 0;
+    for (i, chunk) in chunks.iter().enumerate().skip(1) {
+        if chunk.encoded - chunks[first].encoded >= size {
+            pieces.push((first, i));
+            first = i;
+        }
+    }
+    if first < chunks.len() {
+        pieces.push((first, chunks.len()));
+    }
+    // Carve the output into disjoint regions for each piece
+    let mut jobs = Vec::with_capacity(pieces.len());
+    let mut rest = &mut decoded[..decoded_len];
+    for &(first, last) in &pieces {
+        let out_end = chunks.get(last).map_or(decoded_len, |next| next.decoded);
+        let (head, tail) = rest.split_at_mut(out_end - chunks[first].decoded);
+        jobs.push((&chunks[first..last], head));
+        rest = tail;
+    }
+    // Decode the pieces concurrently and report the earliest failure
+    let errors: Vec<Option<DecodeError>> = jobs
+        .into_par_iter()
+        .map(|(chunks, output)| {
+            let mut output_pos = 0;
+            for chunk in chunks {
+                // Consume the entire chunk, ensuring there's no zero in it
+                let start = chunk.encoded + 1;
+                let run = &data[start..start + chunk.code as usize - 1];
+                if let Some(i) = run.iter().position(|&b| b == 0) {
+                    return Some(DecodeError::ZeroBinary { at: start + i });
+                }
+                output[output_pos..output_pos + run.len()].copy_from_slice(run);
+                output_pos += run.len();
+
+                // If we had a partial chunk followed by more, backfill the zero
+                if output_pos < output.len() && chunk.code != 0xff {
+                    output[output_pos] = 0;
+                    output_pos += 1;
+                }
+            }
+            None
+        })
+        .collect();
+
+    if let Some(err) = errors.into_iter().flatten().next() {
+        return Err(err);
+    }
+    match chain_err {
+        Some(err) => Err(err),
+        None => Ok(decoded_len),
+    }
+}
+
+/// Splits a buffer of 0x00 delimited COBS frames and decodes each of them across
+/// the rayon thread pool. Empty frames (consecutive delimiters) are skipped. The
+/// results are returned in the order the frames appear in the buffer.
+pub fn par_decode_frames(data: &[u8]) -> Vec<Result<Vec<u8>, DecodeError>> {
+    let pieces: Vec<Vec<_>> = frame_splits(data)
+        .par_windows(2)
+        .map(|w| {
+            data[w[0]..w[1]]
+                .split(|&b| b == 0)
+                .filter(|f| !f.is_empty())
+                .map(|frame| {
+                    let mut buf = vec![0u8; decode_buffer(frame.len())];
+                    let len = decode_unsafe(frame, &mut buf)?;
+                    buf.truncate(len);
+                    Ok(buf)
+                })
+                .collect()
+        })
+        .collect();
+    pieces.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::random;
+    use crate::{decode, encode};
+    use rand::Rng;
+
+    #[test]
+    fn test_par_roundtrip_matches_serial() {
+        for size in [
+            0,
+            1000,
+            MIN_PIECE_SIZE + 1,
+            4 * MIN_PIECE_SIZE + 123,
+            1 << 21,
+        ] {
+            for zeros in [0.0, 0.0001, 1.0 / 256.0, 0.5] {
+                let data = random(size, zeros);
+
+                let mut want = vec![0u8; encode_buffer(size)];
+                let want_len = encode(&data, &mut want).unwrap();
+                let mut have = vec![0u8; encode_buffer(size)];
+                let have_len = par_encode(&data, &mut have).unwrap();
+                assert_eq!(
+                    &have[..have_len],
+                    &want[..want_len],
+                    "size {size}, zeros {zeros}"
+                );
+
+                let mut dec = vec![0u8; decode_buffer(have_len)];
+                let dec_len = par_decode(&have[..have_len], &mut dec).unwrap();
+                assert_eq!(&dec[..dec_len], &data[..]);
+            }
+        }
+
+        // Pieces ending in full blocks closed by a zero, with no chunk opened
+        for block in [253, 254, 255] {
+            let mut pattern = vec![1u8; block];
+            pattern.push(0);
+            let data = pattern.repeat(4 * MIN_PIECE_SIZE / pattern.len() + 1);
+
+            let mut want = vec![0u8; encode_buffer(data.len())];
+            let want_len = encode(&data, &mut want).unwrap();
+            let mut have = vec![0u8; encode_buffer(data.len())];
+            let have_len = par_encode(&data, &mut have).unwrap();
+            assert_eq!(&have[..have_len], &want[..want_len], "block {block}");
+        }
+    }
+
+    #[test]
+    fn test_par_decode_errors_match_serial() {
+        let data = random(4 * MIN_PIECE_SIZE, 1.0 / 256.0);
+        let mut enc = vec![0u8; encode_buffer(data.len())];
+        let len = encode(&data, &mut enc).unwrap();
+        enc.truncate(len);
+
+        let mut rng = rand::rng();
+        for _ in 0..50 {
+            // Corrupt a few random bytes, possibly breaking the chain or the data
+            let mut bad = enc.clone();
+            for _ in 0..rng.random_range(1..4) {
+                let at = rng.random_range(0..bad.len());
+                bad[at] = if rng.random_bool(0.5) {
+                    0
+                } else {
+                    rng.random()
+                };
+            }
+            let mut want = vec![0u8; decode_buffer(bad.len())];
+            let want = decode(&bad, &mut want).map(|n| want[..n].to_vec());
+            let mut have = vec![0u8; decode_buffer(bad.len())];
+            let have = par_decode(&bad, &mut have).map(|n| have[..n].to_vec());
+            assert_eq!(have.err(), want.err());
+        }
+    }
+
+    #[test]
+    fn test_encoded_len() {
+        for size in [0, 1, 253, 254, 255, 508, 509, 1000] {
+            for zeros in [0.0, 0.01, 0.5, 1.0] {
+                let mut data = random(size, zeros);
+                for tail in [None, Some(0), Some(1)] {
+                    data.extend(tail);
+                    let mut enc = vec![0u8; encode_buffer(data.len())];
+                    let len = encode(&data, &mut enc).unwrap();
+                    assert_eq!(encoded_len(&data), len, "size {size}, zeros {zeros}");
+                }
+            }
+        }
+        // Runs closed by a zero right after a full block
+        let mut data = vec![1u8; 254];
+        data.push(0);
+        let mut enc = vec![0u8; encode_buffer(data.len())];
+        assert_eq!(encoded_len(&data), encode(&data, &mut enc).unwrap());
+    }
+
+    #[test]
+    fn test_par_decode_frames() {
+        let payloads: Vec<Vec<u8>> = (0..100).map(|i| random(i * 37, 0.1)).collect();
+
+        let mut stream = vec![0u8];
+        for payload in &payloads {
+            let mut enc = vec![0u8; encode_buffer(payload.len())];
+            let len = encode(payload, &mut enc).unwrap();
+            stream.extend_from_slice(&enc[..len]);
+            stream.push(0);
+        }
+        stream.extend_from_slice(&[0x03, 0x01]);
+
+        let frames = par_decode_frames(&stream);
+        assert_eq!(frames.len(), payloads.len() + 1);
+        for (frame, payload) in frames.iter().zip(&payloads) {
+            assert_eq!(frame.as_ref().unwrap(), payload);
+        }
+        assert!(matches!(
+            frames[100],
+            Err(DecodeError::ChunkOverflow { .. })
+        ));
+    }
+}
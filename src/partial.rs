@@ -0,0 +1,176 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+/// Resume point of an encoding split across multiple output buffers, created by
+/// [`EncodeCursor::new`] and advanced by [`encode_partial`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeCursor {
+    pos: usize,      // Input position of the current chunk's data
+    sent: usize,     // Bytes of the current chunk already emitted, code included
+    run: u8,         // Data run of the current chunk, once its code was emitted
+    open: bool,      // Whether there's a chunk left to emit
+    delimited: bool, // Whether the trailing delimiter was emitted
+}
+
+impl EncodeCursor {
+    /// Creates a cursor positioned at the start of an encoding.
+    pub const fn new() -> Self {
+        Self {
+            pos: 0,
+            sent: 0,
+            run: 0,
+            open: true,
+            delimited: false,
+        }
+    }
+
+    /// Returns whether the entire encoding, delimiter included, was emitted.
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        !self.open && self.delimited
+    }
+}
+
+impl Default for EncodeCursor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Encodes an opaque data blob with COBS using 0 as the sentinel value, writing
+/// only as much as fits into `out`. Returns the number of bytes written and the
+/// cursor to resume from with the next output buffer.
+///
+/// The encoding is terminated by a 0x00 delimiter, so the concatenation of all
+/// the written buffers equals [`encode`](crate::encode)'s output followed by a
+/// zero. This allows streaming a frame over fixed size packets (USB endpoints,
+/// BLE notifications), feeding each into a [`FrameBuffer`](crate::FrameBuffer)
+/// on the receiving side to reassemble it.
+///
+/// Calling with the same cursor and data is idempotent, so a packet that failed
+/// to send can be regenerated by simply retrying with the previous cursor.
+pub fn encode_partial(data: &[u8], cursor: EncodeCursor, out: &mut [u8]) -> (usize, EncodeCursor) {
+    let mut cursor = cursor;
+    let mut written = 0usize;
+
+    while written < out.len() {
+        // If all the chunks are done, terminate the frame
+        if !cursor.open {
+            if !cursor.delimited {
+                out[written] = 0;
+                written += 1;
+                cursor.delimited = true;
+            }
+            break;
+        }
+        // Figure out the extent of the current chunk and emit the code byte if
+        // it wasn't yet. The extent is kept in the cursor, so long runs split
+        // across many small packets are only scanned once.
+        if cursor.sent == 0 {
+            cursor.run = run_length(data, cursor.pos) as u8;
+            out[written] = cursor.run + 1;
+            written += 1;
+            cursor.sent = 1;
+        }
+        // Emit as much of the chunk's data as fits
+        let run = cursor.run as usize;
+        let done = cursor.sent - 1;
+        let n = (run - done).min(out.len() - written);
+        let start = cursor.pos + done;
+
+        out[written..written + n].copy_from_slice(&data[start..start + n]);
+        written += n;
+        cursor.sent += n;
+
+        // If the chunk was fully emitted, move on to the next one
+        if cursor.sent == run + 1 {
+            (cursor.pos, cursor.open) = next_chunk(data, cursor.pos, run);
+            cursor.sent = 0;
+        }
+    }
+    (written, cursor)
+}
+
+// Computes the length of the data run of the chunk starting at the given input
+// position, which is at most 254 bytes.
+#[inline]
+fn run_length(data: &[u8], pos: usize) -> usize {
+    let rest = &data[pos..];
+    rest.iter()
+        .take(254)
+        .position(|&b| b == 0)
+        .unwrap_or(rest.len().min(254))
+}
+
+// Computes where the chunk following the one at the given input position and of
+// the given data run starts, and whether there is a next chunk at all.
+#[inline]
+fn next_chunk(data: &[u8], pos: usize, run: usize) -> (usize, bool) {
+    if run == 254 {
+        // An entire chunk was non-zero, only open another if there's more data
+        (pos + run, pos + run < data.len())
+    } else if pos + run < data.len() {
+        // Chunk terminated by a zero, the next chunk starts after it
+        (pos + run + 1, true)
+    } else {
+        // Chunk terminated by the end of the data
+        (pos + run, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::random;
+    use crate::{FrameBuffer, encode, encode_buffer};
+
+    #[test]
+    fn test_encode_partial_matches_encode() {
+        for size in [0, 1, 20, 253, 254, 255, 508, 1000] {
+            for zeros in [0.0, 0.01, 0.5] {
+                let data = random(size, zeros);
+
+                let mut want = vec![0u8; encode_buffer(size)];
+                let len = encode(&data, &mut want).unwrap();
+                want.truncate(len);
+                want.push(0);
+
+                for mtu in [1, 2, 20, 64, 244, 2048] {
+                    let mut have = Vec::new();
+                    let mut cursor = EncodeCursor::new();
+                    while !cursor.is_done() {
+                        let mut packet = vec![0u8; mtu];
+                        let (n, next) = encode_partial(&data, cursor, &mut packet);
+                        have.extend_from_slice(&packet[..n]);
+                        cursor = next;
+                    }
+                    assert_eq!(have, want, "size {size}, mtu {mtu}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_partial_reassembly() {
+        let data: Vec<u8> = (0..500u32).map(|i| (i % 7) as u8).collect();
+        let mut fb = FrameBuffer::<512>::new();
+
+        let mut frames = Vec::new();
+        let mut cursor = EncodeCursor::new();
+        while !cursor.is_done() {
+            let mut packet = [0u8; 64];
+            let (n, next) = encode_partial(&data, cursor, &mut packet);
+            cursor = next;
+
+            let mut rest = &packet[..n];
+            while !rest.is_empty() {
+                let (used, frame) = fb.push_slice(rest);
+                if let Some(frame) = frame {
+                    frames.push(frame.unwrap().to_vec());
+                }
+                rest = &rest[used..];
+            }
+        }
+        assert_eq!(frames, vec![data]);
+    }
+}
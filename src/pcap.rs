@@ -0,0 +1,207 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Export of frame streams into pcapng captures, for inspection in Wireshark
+//! and friends.
+//!
+//! Each frame becomes one packet on a single interface. Frames that fail to
+//! decode are written out in their encoded form, with a packet comment naming
+//! the error, so that nothing on the wire is lost from the capture.
+
+use crate::{DecodeError, decode, decode_buffer};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Link-layer type reserved for private use, the default for COBS payloads.
+pub const LINKTYPE_USER0: u16 = 147;
+
+// Block types and option codes of the pcapng format
+const SECTION_HEADER: u32 = 0x0a0d0d0a;
+const INTERFACE_DESCRIPTION: u32 = 0x00000001;
+const ENHANCED_PACKET: u32 = 0x00000006;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+
+/// Writer of pcapng captures with one interface, timestamped in microseconds.
+#[derive(Debug)]
+pub struct PcapWriter<W: Write> {
+    inner: W,
+    block: Vec<u8>, // Scratch space for assembling blocks
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Creates a capture on top of a byte stream, writing out the section and
+    /// interface headers for the given link-layer type.
+    pub fn new(inner: W, linktype: u16) -> io::Result<Self> {
+        let mut writer = Self {
+            inner,
+            block: Vec::new(),
+        };
+        let mut body = Vec::with_capacity(16);
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes()); // Major version
+        body.extend_from_slice(&0u16.to_le_bytes()); // Minor version
+        body.extend_from_slice(&(-1i64).to_le_bytes()); // Unknown section length
+        writer.write_block(SECTION_HEADER, &body, None)?;
+
+        let mut body = Vec::with_capacity(8);
+        body.extend_from_slice(&linktype.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes()); // Reserved
+        body.extend_from_slice(&0u32.to_le_bytes()); // No snapshot length limit
+        writer.write_block(INTERFACE_DESCRIPTION, &body, None)?;
+        Ok(writer)
+    }
+
+    /// Writes a packet into the capture, with an optional timestamp (zero if
+    /// missing) and comment.
+    pub fn write_packet(
+        &mut self,
+        data: &[u8],
+        timestamp: Option<SystemTime>,
+        comment: Option<&str>,
+    ) -> io::Result<()> {
+        let micros = timestamp
+            .and_then(|ts| ts.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_micros() as u64);
+
+        let mut body = Vec::with_capacity(20 + data.len() + 3);
+        body.extend_from_slice(&0u32.to_le_bytes()); // Interface id
+        body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(micros as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes()); // Captured
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes()); // Original
+        body.extend_from_slice(data);
+        pad(&mut body);
+
+        self.write_block(ENHANCED_PACKET, &body, comment)
+    }
+
+    /// Decodes a frame (without the 0x00 delimiter) and writes the payload into
+    /// the capture. Frames that fail to decode are written as they are, with a
+    /// comment naming the error, which is also returned.
+    pub fn write_frame(
+        &mut self,
+        encoded: &[u8],
+        timestamp: Option<SystemTime>,
+    ) -> io::Result<Option<DecodeError>> {
+        let mut decoded = vec![0u8; decode_buffer(encoded.len())];
+        match decode(encoded, &mut decoded) {
+            Ok(len) => {
+                self.write_packet(&decoded[..len], timestamp, None)?;
+                Ok(None)
+            }
+            Err(err) => {
+                let comment = format!("cobs decode failed: {err}");
+                self.write_packet(encoded, timestamp, Some(&comment))?;
+                Ok(Some(err))
+            }
+        }
+    }
+
+    /// Flushes the underlying stream.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Unwraps the writer, returning the underlying stream.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    // Writes a block of the given type, wrapping the body with the lengths and
+    // appending the comment option if any
+    fn write_block(&mut self, kind: u32, body: &[u8], comment: Option<&str>) -> io::Result<()> {
+        let block = &mut self.block;
+        block.clear();
+        block.extend_from_slice(&kind.to_le_bytes());
+        block.extend_from_slice(&0u32.to_le_bytes()); // Length, patched below
+        block.extend_from_slice(body);
+        if let Some(comment) = comment {
+            block.extend_from_slice(&OPT_COMMENT.to_le_bytes());
+            block.extend_from_slice(&(comment.len() as u16).to_le_bytes());
+            block.extend_from_slice(comment.as_bytes());
+            pad(block);
+            block.extend_from_slice(&OPT_END.to_le_bytes());
+            block.extend_from_slice(&0u16.to_le_bytes());
+        }
+        let len = (block.len() + 4) as u32;
+        block[4..8].copy_from_slice(&len.to_le_bytes());
+        block.extend_from_slice(&len.to_le_bytes());
+        self.inner.write_all(block)
+    }
+}
+
+// Pads a buffer with zeroes up to a 32 bit boundary
+fn pad(buf: &mut Vec<u8>) {
+    buf.resize(buf.len().next_multiple_of(4), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Splits a capture into its blocks, checking the framing of each
+    fn blocks(mut data: &[u8]) -> Vec<(u32, &[u8])> {
+        let mut out = Vec::new();
+        while !data.is_empty() {
+            let kind = u32::from_le_bytes(data[..4].try_into().unwrap());
+            let len = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+            assert_eq!(len % 4, 0);
+            assert_eq!(&data[len - 4..len], &data[4..8]);
+            out.push((kind, &data[8..len - 4]));
+            data = &data[len..];
+        }
+        out
+    }
+
+    #[test]
+    fn test_pcap_layout() {
+        let mut pcap = PcapWriter::new(Vec::new(), LINKTYPE_USER0).unwrap();
+        let ts = UNIX_EPOCH + Duration::from_micros(0x1_0000_0002);
+        assert_eq!(
+            pcap.write_frame(&[0x02, 0x11, 0x01], Some(ts)).unwrap(),
+            None
+        );
+        assert_eq!(
+            pcap.write_frame(&[0x05, 0x11], None).unwrap(),
+            Some(DecodeError::ChunkOverflow {
+                at: 0,
+                marker: 5,
+                len: 2
+            })
+        );
+        let capture = pcap.into_inner();
+        let blocks = blocks(&capture);
+        assert_eq!(blocks.len(), 4);
+
+        assert_eq!(blocks[0].0, SECTION_HEADER);
+        assert_eq!(&blocks[0].1[..4], &BYTE_ORDER_MAGIC.to_le_bytes());
+        assert_eq!(
+            blocks[1],
+            (INTERFACE_DESCRIPTION, &[147, 0, 0, 0, 0, 0, 0, 0][..])
+        );
+
+        // Decoded frame with its timestamp split into halves
+        let (kind, body) = blocks[2];
+        assert_eq!(kind, ENHANCED_PACKET);
+        assert_eq!(&body[4..12], &[1, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(&body[12..20], &[2, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(&body[20..], &[0x11, 0x00, 0x00, 0x00]);
+
+        // Broken frame kept encoded, with the error as a comment
+        let (_, body) = blocks[3];
+        assert_eq!(&body[4..12], &[0; 8]);
+        assert_eq!(&body[20..24], &[0x05, 0x11, 0x00, 0x00]);
+        let comment_len = u16::from_le_bytes([body[26], body[27]]) as usize;
+        let comment = std::str::from_utf8(&body[28..28 + comment_len]).unwrap();
+        assert!(comment.starts_with("cobs decode failed: chunk overflow"));
+        assert_eq!(&body[body.len() - 4..], &[0; 4]);
+    }
+}
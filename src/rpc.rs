@@ -0,0 +1,859 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Request/response RPC over COBS links.
+//!
+//! Methods and notification topics are declared as types implementing [`Method`]
+//! and [`Topic`], which bind a numeric identifier to the [`Wire`] types carried.
+//! A [`Router`] dispatches requests to typed handlers, while clients correlate
+//! the replies to the calls by a request identifier. Servers may also push
+//! notifications at any time, which clients receive interleaved with replies.
+//!
+//! Blocking variants ([`Client`], [`Server`]) run over [`std::io`] streams and
+//! async variants ([`AsyncClient`], [`AsyncServer`]) over tokio streams.
+//!
+//! Wire format of the frame payloads:
+//! - Request: `0x00`, request id (u32 BE), method (u16 BE), body
+//! - Response: `0x01`, request id (u32 BE), body
+//! - Error: `0x02`, request id (u32 BE), code (u16 BE), UTF-8 message
+//! - Notification: `0x03`, topic (u16 BE), body
+
+use crate::io::{FrameReader, FrameWriter};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(feature = "tokio")]
+use {
+    crate::codec::AsyncFrameReader,
+    tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    tokio::sync::oneshot,
+};
+
+/// Type byte of a request frame.
+const KIND_REQUEST: u8 = 0x00;
+
+/// Type byte of a successful response frame.
+const KIND_RESPONSE: u8 = 0x01;
+
+/// Type byte of an error response frame.
+const KIND_ERROR: u8 = 0x02;
+
+/// Type byte of a notification frame.
+const KIND_NOTIFICATION: u8 = 0x03;
+
+/// Types that can be carried in the body of an RPC frame.
+pub trait Wire: Sized {
+    /// Appends the binary form of the value to a buffer.
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Parses a value from its binary form, or returns `None` if malformed.
+    fn decode(data: &[u8]) -> Option<Self>;
+}
+
+impl Wire for () {
+    fn encode(&self, _: &mut Vec<u8>) {}
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        data.is_empty().then_some(())
+    }
+}
+
+impl Wire for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        Some(data.to_vec())
+    }
+}
+
+impl Wire for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        String::from_utf8(data.to_vec()).ok()
+    }
+}
+
+// Implements the wire encoding of integers, most significant byte first
+macro_rules! impl_wire_int {
+    ($($t:ty),*) => {$(
+        impl Wire for $t {
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_be_bytes());
+            }
+
+            fn decode(data: &[u8]) -> Option<Self> {
+                data.try_into().ok().map(<$t>::from_be_bytes)
+            }
+        }
+    )*};
+}
+
+impl_wire_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Remotely callable method.
+pub trait Method {
+    /// Identifier of the method on the wire.
+    const ID: u16;
+    /// Parameters of a call.
+    type Request: Wire;
+    /// Result of a successful call.
+    type Response: Wire;
+}
+
+/// Topic of notifications pushed by a server.
+pub trait Topic {
+    /// Identifier of the topic on the wire.
+    const ID: u16;
+    /// Content of a notification.
+    type Body: Wire;
+}
+
+/// Error reply produced by a method handler (or by the router itself).
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("remote error {code}: {message}")]
+pub struct RemoteError {
+    pub code: u16,
+    pub message: String,
+}
+
+impl RemoteError {
+    /// Code replied when the called method is not registered.
+    pub const UNKNOWN_METHOD: u16 = 0xffff;
+
+    /// Code replied when the request body cannot be parsed.
+    pub const BAD_REQUEST: u16 = 0xfffe;
+
+    /// Creates an error reply with an application defined code.
+    pub fn new(code: u16, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Error types that can be returned from RPC calls.
+#[derive(Debug, thiserror::Error)]
+pub enum RpcError {
+    #[error("request timed out")]
+    Timeout,
+    #[error("connection closed")]
+    Closed,
+    #[error("malformed reply")]
+    Malformed,
+    #[error(transparent)]
+    Remote(#[from] RemoteError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Notification pushed by a server, tagged with its topic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub topic: u16,
+    pub body: Vec<u8>,
+}
+
+impl Notification {
+    /// Parses the body as the given topic's type. Returns `None` if the topic
+    /// does not match or if the body is malformed.
+    pub fn parse<T: Topic>(&self) -> Option<T::Body> {
+        if self.topic != T::ID {
+            return None;
+        }
+        T::Body::decode(&self.body)
+    }
+}
+
+/// Decoded frame payload of the RPC protocol.
+#[derive(Debug, PartialEq, Eq)]
+enum Message {
+    Request { id: u32, method: u16, body: Vec<u8> },
+    Response { id: u32, result: Reply },
+    Notification(Notification),
+}
+
+impl Message {
+    // Serializes the message into a frame payload
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Message::Request { id, method, body } => {
+                out.push(KIND_REQUEST);
+                out.extend_from_slice(&id.to_be_bytes());
+                out.extend_from_slice(&method.to_be_bytes());
+                out.extend_from_slice(body);
+            }
+            Message::Response {
+                id,
+                result: Ok(body),
+            } => {
+                out.push(KIND_RESPONSE);
+                out.extend_from_slice(&id.to_be_bytes());
+                out.extend_from_slice(body);
+            }
+            Message::Response {
+                id,
+                result: Err(err),
+            } => {
+                out.push(KIND_ERROR);
+                out.extend_from_slice(&id.to_be_bytes());
+                out.extend_from_slice(&err.code.to_be_bytes());
+                out.extend_from_slice(err.message.as_bytes());
+            }
+            Message::Notification(n) => {
+                out.push(KIND_NOTIFICATION);
+                out.extend_from_slice(&n.topic.to_be_bytes());
+                out.extend_from_slice(&n.body);
+            }
+        }
+        out
+    }
+
+    // Parses a frame payload into a message, or returns `None` if malformed
+    fn decode(frame: &[u8]) -> Option<Message> {
+        let (&kind, rest) = frame.split_first()?;
+        let word = |data: &[u8]| data.get(..2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+        let dword = |data: &[u8]| {
+            data.get(..4)
+                .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        };
+
+        match kind {
+            KIND_REQUEST => Some(Message::Request {
+                id: dword(rest)?,
+                method: word(&rest[4..])?,
+                body: rest[6..].to_vec(),
+            }),
+            KIND_RESPONSE => Some(Message::Response {
+                id: dword(rest)?,
+                result: Ok(rest[4..].to_vec()),
+            }),
+            KIND_ERROR => Some(Message::Response {
+                id: dword(rest)?,
+                result: Err(RemoteError {
+                    code: word(&rest[4..])?,
+                    message: String::from_utf8_lossy(&rest[6..]).into_owned(),
+                }),
+            }),
+            KIND_NOTIFICATION => Some(Message::Notification(Notification {
+                topic: word(rest)?,
+                body: rest[2..].to_vec(),
+            })),
+            _ => None,
+        }
+    }
+}
+
+/// Type erased method handler, taking and returning serialized bodies.
+type Handler = Box<dyn Fn(&[u8]) -> Result<Vec<u8>, RemoteError> + Send + Sync>;
+
+/// Dispatcher of requests to typed method handlers.
+#[derive(Default)]
+pub struct Router {
+    methods: HashMap<u16, Handler>,
+}
+
+impl Router {
+    /// Creates a router without any methods.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers (or replaces) the handler of a method.
+    pub fn register<M, F>(&mut self, handler: F) -> &mut Self
+    where
+        M: Method,
+        F: Fn(M::Request) -> Result<M::Response, RemoteError> + Send + Sync + 'static,
+    {
+        let handler = move |body: &[u8]| {
+            let request = M::Request::decode(body)
+                .ok_or_else(|| RemoteError::new(RemoteError::BAD_REQUEST, "malformed request"))?;
+            let mut out = Vec::new();
+            handler(request)?.encode(&mut out);
+            Ok(out)
+        };
+        self.methods.insert(M::ID, Box::new(handler));
+        self
+    }
+
+    /// Handles a frame payload, returning the reply payload if it was a request.
+    /// Anything else is ignored.
+    pub fn handle(&self, frame: &[u8]) -> Option<Vec<u8>> {
+        let Some(Message::Request { id, method, body }) = Message::decode(frame) else {
+            return None;
+        };
+        let result = match self.methods.get(&method) {
+            Some(handler) => handler(&body),
+            None => Err(RemoteError::new(
+                RemoteError::UNKNOWN_METHOD,
+                format!("unknown method {method}"),
+            )),
+        };
+        Some(Message::Response { id, result }.encode())
+    }
+}
+
+/// Outcome of a call as carried by a response frame.
+type Reply = Result<Vec<u8>, RemoteError>;
+
+/// Calls awaiting their replies, keyed by request id.
+struct Pending<S> {
+    next_id: u32,
+    waiters: HashMap<u32, S>,
+    closed: bool, // Whether the reader stopped, so no reply can arrive anymore
+}
+
+impl<S> Pending<S> {
+    fn new() -> Self {
+        Self {
+            next_id: 0,
+            waiters: HashMap::new(),
+            closed: false,
+        }
+    }
+
+    // Registers a waiter for the next request, returning its id
+    fn insert(&mut self, waiter: S) -> Result<u32, RpcError> {
+        if self.closed {
+            return Err(RpcError::Closed);
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.waiters.insert(id, waiter);
+        Ok(id)
+    }
+
+    // Drops all waiters, failing their calls
+    fn close(&mut self) {
+        self.closed = true;
+        self.waiters.clear();
+    }
+}
+
+// Serializes a request into a frame payload
+fn request<M: Method>(id: u32, req: &M::Request) -> Vec<u8> {
+    let mut body = Vec::new();
+    req.encode(&mut body);
+    Message::Request {
+        id,
+        method: M::ID,
+        body,
+    }
+    .encode()
+}
+
+// Serializes a notification into a frame payload
+fn notification<T: Topic>(body: &T::Body) -> Vec<u8> {
+    let mut out = Vec::new();
+    body.encode(&mut out);
+    Message::Notification(Notification {
+        topic: T::ID,
+        body: out,
+    })
+    .encode()
+}
+
+// Parses the reply of a call into the method's response type
+fn response<M: Method>(result: Reply) -> Result<M::Response, RpcError> {
+    M::Response::decode(&result?).ok_or(RpcError::Malformed)
+}
+
+/// Blocking RPC client. Replies and notifications are read by a background
+/// thread, so calls can be made concurrently from multiple threads.
+///
+/// The thread runs until the reader ends or fails, which a blocking reader can't
+/// be made to do from this side. Dropping the client detaches the thread, so it
+/// lingers until the transport is closed; use [`close`](Self::close) to wait for
+/// it instead.
+pub struct Client<W: Write> {
+    writer: Mutex<FrameWriter<W>>,
+    pending: Arc<Mutex<Pending<mpsc::Sender<Reply>>>>,
+    notifications: Mutex<mpsc::Receiver<Notification>>,
+    reader: thread::JoinHandle<()>,
+}
+
+impl<W: Write> Client<W> {
+    /// Creates a client sending requests into `writer` and reading the replies
+    /// and notifications from `reader` on a background thread.
+    pub fn new<R: Read + Send + 'static>(reader: R, writer: W) -> Self {
+        let pending = Arc::new(Mutex::new(Pending::<mpsc::Sender<Reply>>::new()));
+        let (notify_tx, notify_rx) = mpsc::channel();
+
+        let waiters = pending.clone();
+        let reader = thread::spawn(move || {
+            for frame in FrameReader::new(reader) {
+                let frame = match frame {
+                    Ok(frame) => frame,
+                    Err(err) if err.kind() == io::ErrorKind::InvalidData => continue,
+                    Err(_) => break,
+                };
+                match Message::decode(&frame) {
+                    Some(Message::Response { id, result }) => {
+                        if let Some(waiter) = waiters.lock().unwrap().waiters.remove(&id) {
+                            let _ = waiter.send(result);
+                        }
+                    }
+                    Some(Message::Notification(n)) => {
+                        let _ = notify_tx.send(n);
+                    }
+                    _ => continue,
+                }
+            }
+            waiters.lock().unwrap().close();
+        });
+        Self {
+            writer: Mutex::new(FrameWriter::new(writer)),
+            pending,
+            notifications: Mutex::new(notify_rx),
+            reader,
+        }
+    }
+
+    /// Closes the client, dropping the writer and waiting for the reader thread
+    /// to exit. The thread exits once the reader ends, so either the remote side
+    /// must close its end in turn (as a [`Server`] does once its input ends), or
+    /// the caller must shut the transport down, otherwise this blocks.
+    pub fn close(self) {
+        drop(self.writer);
+        let _ = self.reader.join();
+    }
+
+    /// Calls a remote method and waits for its reply, up to a timeout.
+    pub fn call<M: Method>(
+        &self,
+        req: &M::Request,
+        timeout: Duration,
+    ) -> Result<M::Response, RpcError> {
+        let (tx, rx) = mpsc::channel();
+        let id = self.pending.lock().unwrap().insert(tx)?;
+
+        let sent = {
+            let mut writer = self.writer.lock().unwrap();
+            writer
+                .write_frame(&request::<M>(id, req))
+                .and_then(|_| writer.flush())
+        };
+        if let Err(err) = sent {
+            self.pending.lock().unwrap().waiters.remove(&id);
+            return Err(err.into());
+        }
+        match rx.recv_timeout(timeout) {
+            Ok(result) => response::<M>(result),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                self.pending.lock().unwrap().waiters.remove(&id);
+                Err(RpcError::Timeout)
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(RpcError::Closed),
+        }
+    }
+
+    /// Waits for the next notification pushed by the server, up to a timeout.
+    pub fn recv_notification(&self, timeout: Duration) -> Result<Notification, RpcError> {
+        match self.notifications.lock().unwrap().recv_timeout(timeout) {
+            Ok(n) => Ok(n),
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(RpcError::Timeout),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(RpcError::Closed),
+        }
+    }
+}
+
+/// Blocking RPC server.
+pub struct Server<W: Write> {
+    router: Router,
+    writer: Mutex<FrameWriter<W>>,
+}
+
+impl<W: Write> Server<W> {
+    /// Creates a server replying to requests (and pushing notifications) into
+    /// `writer`.
+    pub fn new(router: Router, writer: W) -> Self {
+        Self {
+            router,
+            writer: Mutex::new(FrameWriter::new(writer)),
+        }
+    }
+
+    /// Pushes a notification to the client.
+    pub fn notify<T: Topic>(&self, body: &T::Body) -> io::Result<()> {
+        self.send(&notification::<T>(body))
+    }
+
+    /// Serves the requests read from `reader` until the stream ends. Malformed
+    /// frames are skipped.
+    pub fn serve<R: Read>(&self, reader: R) -> io::Result<()> {
+        for frame in FrameReader::new(reader) {
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => continue,
+                Err(err) => return Err(err),
+            };
+            if let Some(reply) = self.router.handle(&frame) {
+                self.send(&reply)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Writes a frame payload and flushes it out
+    fn send(&self, payload: &[u8]) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_frame(payload)?;
+        writer.flush()
+    }
+}
+
+// Encodes a frame payload into a delimited COBS frame
+#[cfg(feature = "tokio")]
+fn delimit(payload: &[u8]) -> Vec<u8> {
+    let mut out = vec![0u8; crate::encode_buffer(payload.len()) + 1];
+    let len = crate::encode_delimited(payload, &mut out);
+    out.truncate(len);
+    out
+}
+
+/// Async RPC client. Replies and notifications are read by a background task,
+/// so calls can be made concurrently.
+#[cfg(feature = "tokio")]
+pub struct AsyncClient<W: AsyncWrite + Unpin> {
+    writer: tokio::sync::Mutex<W>,
+    pending: Arc<Mutex<Pending<oneshot::Sender<Reply>>>>,
+    notifications: tokio::sync::Mutex<tokio::sync::mpsc::UnboundedReceiver<Notification>>,
+}
+
+#[cfg(feature = "tokio")]
+impl<W: AsyncWrite + Unpin> AsyncClient<W> {
+    /// Creates a client sending requests into `writer` and reading the replies
+    /// and notifications from `reader` on a background task.
+    ///
+    /// # Panics
+    /// Panics if called outside of a tokio runtime.
+    pub fn new<R: AsyncRead + Unpin + Send + 'static>(reader: R, writer: W) -> Self {
+        let pending = Arc::new(Mutex::new(Pending::<oneshot::Sender<Reply>>::new()));
+        let (notify_tx, notify_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let waiters = pending.clone();
+        tokio::spawn(async move {
+            let mut frames = AsyncFrameReader::new(reader);
+            loop {
+                let frame = match frames.read_frame().await {
+                    Ok(Some(frame)) => frame,
+                    Err(err) if err.kind() == io::ErrorKind::InvalidData => continue,
+                    _ => break,
+                };
+                match Message::decode(&frame) {
+                    Some(Message::Response { id, result }) => {
+                        if let Some(waiter) = waiters.lock().unwrap().waiters.remove(&id) {
+                            let _ = waiter.send(result);
+                        }
+                    }
+                    Some(Message::Notification(n)) => {
+                        let _ = notify_tx.send(n);
+                    }
+                    _ => continue,
+                }
+            }
+            waiters.lock().unwrap().close();
+        });
+        Self {
+            writer: tokio::sync::Mutex::new(writer),
+            pending,
+            notifications: tokio::sync::Mutex::new(notify_rx),
+        }
+    }
+
+    /// Calls a remote method and waits for its reply, up to a timeout.
+    pub async fn call<M: Method>(
+        &self,
+        req: &M::Request,
+        timeout: Duration,
+    ) -> Result<M::Response, RpcError> {
+        let (tx, rx) = oneshot::channel();
+        let id = self.pending.lock().unwrap().insert(tx)?;
+
+        let sent = {
+            let mut writer = self.writer.lock().await;
+            match writer.write_all(&delimit(&request::<M>(id, req))).await {
+                Ok(()) => writer.flush().await,
+                Err(err) => Err(err),
+            }
+        };
+        if let Err(err) = sent {
+            self.pending.lock().unwrap().waiters.remove(&id);
+            return Err(err.into());
+        }
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(result)) => response::<M>(result),
+            Ok(Err(_)) => Err(RpcError::Closed),
+            Err(_) => {
+                self.pending.lock().unwrap().waiters.remove(&id);
+                Err(RpcError::Timeout)
+            }
+        }
+    }
+
+    /// Waits for the next notification pushed by the server, up to a timeout.
+    pub async fn recv_notification(&self, timeout: Duration) -> Result<Notification, RpcError> {
+        let mut notifications = self.notifications.lock().await;
+        match tokio::time::timeout(timeout, notifications.recv()).await {
+            Ok(Some(n)) => Ok(n),
+            Ok(None) => Err(RpcError::Closed),
+            Err(_) => Err(RpcError::Timeout),
+        }
+    }
+}
+
+/// Async RPC server.
+#[cfg(feature = "tokio")]
+pub struct AsyncServer<W: AsyncWrite + Unpin> {
+    router: Router,
+    writer: tokio::sync::Mutex<W>,
+}
+
+#[cfg(feature = "tokio")]
+impl<W: AsyncWrite + Unpin> AsyncServer<W> {
+    /// Creates a server replying to requests (and pushing notifications) into
+    /// `writer`.
+    pub fn new(router: Router, writer: W) -> Self {
+        Self {
+            router,
+            writer: tokio::sync::Mutex::new(writer),
+        }
+    }
+
+    /// Pushes a notification to the client.
+    pub async fn notify<T: Topic>(&self, body: &T::Body) -> io::Result<()> {
+        self.send(&notification::<T>(body)).await
+    }
+
+    /// Serves the requests read from `reader` until the stream ends. Malformed
+    /// frames are skipped.
+    pub async fn serve<R: AsyncRead + Unpin>(&self, reader: R) -> io::Result<()> {
+        let mut frames = AsyncFrameReader::new(reader);
+        loop {
+            let frame = match frames.read_frame().await {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return Ok(()),
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => continue,
+                Err(err) => return Err(err),
+            };
+            if let Some(reply) = self.router.handle(&frame) {
+                self.send(&reply).await?;
+            }
+        }
+    }
+
+    // Writes a frame payload and flushes it out
+    async fn send(&self, payload: &[u8]) -> io::Result<()> {
+        let mut writer = self.writer.lock().await;
+        writer.write_all(&delimit(payload)).await?;
+        writer.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo;
+
+    impl Method for Echo {
+        const ID: u16 = 1;
+        type Request = Vec<u8>;
+        type Response = Vec<u8>;
+    }
+
+    struct Halve;
+
+    impl Method for Halve {
+        const ID: u16 = 2;
+        type Request = u32;
+        type Response = u32;
+    }
+
+    struct Missing;
+
+    impl Method for Missing {
+        const ID: u16 = 3;
+        type Request = ();
+        type Response = ();
+    }
+
+    struct Tick;
+
+    impl Topic for Tick {
+        const ID: u16 = 1;
+        type Body = u64;
+    }
+
+    // Creates a router with the test methods registered
+    fn router() -> Router {
+        let mut router = Router::new();
+        router
+            .register::<Echo, _>(Ok)
+            .register::<Halve, _>(|n| match n % 2 {
+                0 => Ok(n / 2),
+                _ => Err(RemoteError::new(7, format!("{n} is odd"))),
+            });
+        router
+    }
+
+    #[test]
+    fn test_router_dispatch() {
+        let router = router();
+        for msg in [
+            Message::Request {
+                id: 1,
+                method: Halve::ID,
+                body: vec![1, 2],
+            },
+            Message::Request {
+                id: 2,
+                method: Missing::ID,
+                body: vec![],
+            },
+        ] {
+            let reply = Message::decode(&router.handle(&msg.encode()).unwrap()).unwrap();
+            let Message::Response { result, .. } = reply else {
+                panic!("unexpected reply {reply:?}");
+            };
+            assert!(result.is_err());
+        }
+        let notify = notification::<Tick>(&7);
+        assert_eq!(router.handle(&notify), None);
+        assert_eq!(router.handle(&[0x09, 0x01]), None);
+        assert_eq!(router.handle(&[KIND_REQUEST, 0x00]), None);
+    }
+
+    #[test]
+    fn test_rpc_blocking() {
+        let (client_rx, server_tx) = io::pipe().unwrap();
+        let (server_rx, client_tx) = io::pipe().unwrap();
+
+        let server = Arc::new(Server::new(router(), server_tx));
+        let serving = server.clone();
+        let handle = thread::spawn(move || serving.serve(server_rx));
+
+        let client = Arc::new(Client::new(client_rx, client_tx));
+        let timeout = Duration::from_secs(5);
+
+        // Run concurrent calls, interleaved with notifications
+        let callers: Vec<_> = (0..4u32)
+            .map(|t| {
+                let client = client.clone();
+                thread::spawn(move || {
+                    for i in 0..50u32 {
+                        let n = (t * 1000 + i) * 2;
+                        assert_eq!(client.call::<Halve>(&n, timeout).unwrap(), n / 2);
+                        let data = vec![(i % 3) as u8; i as usize];
+                        assert_eq!(client.call::<Echo>(&data, timeout).unwrap(), data);
+                    }
+                })
+            })
+            .collect();
+        for i in 0..10 {
+            server.notify::<Tick>(&i).unwrap();
+        }
+        for caller in callers {
+            caller.join().unwrap();
+        }
+        for i in 0..10 {
+            let n = client.recv_notification(timeout).unwrap();
+            assert_eq!(n.parse::<Tick>(), Some(i));
+        }
+        // Check error replies and timeouts
+        match client.call::<Halve>(&3, timeout) {
+            Err(RpcError::Remote(err)) => assert_eq!(err, RemoteError::new(7, "3 is odd")),
+            res => panic!("unexpected result {res:?}"),
+        }
+        match client.call::<Missing>(&(), timeout) {
+            Err(RpcError::Remote(err)) => assert_eq!(err.code, RemoteError::UNKNOWN_METHOD),
+            res => panic!("unexpected result {res:?}"),
+        }
+        assert!(matches!(
+            client.recv_notification(Duration::from_millis(10)),
+            Err(RpcError::Timeout)
+        ));
+        // Closing the client ends the server, which in turn ends the client
+        drop(server);
+        Arc::into_inner(client).unwrap().close();
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_rpc_blocking_timeout() {
+        let (client_rx, server_tx) = io::pipe().unwrap();
+        let client = Client::new(client_rx, Vec::new());
+
+        assert!(matches!(
+            client.call::<Echo>(&vec![1], Duration::from_millis(20)),
+            Err(RpcError::Timeout)
+        ));
+        drop(server_tx);
+        assert!(matches!(
+            client.recv_notification(Duration::from_secs(5)),
+            Err(RpcError::Closed)
+        ));
+        assert!(matches!(
+            client.call::<Echo>(&vec![1], Duration::from_secs(5)),
+            Err(RpcError::Closed)
+        ));
+        client.close();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_rpc_async() {
+        let (client_tx, server_rx) = tokio::io::duplex(64);
+        let (server_tx, client_rx) = tokio::io::duplex(64);
+
+        let server = Arc::new(AsyncServer::new(router(), server_tx));
+        let serving = server.clone();
+        let handle = tokio::spawn(async move { serving.serve(server_rx).await });
+
+        let client = AsyncClient::new(client_rx, client_tx);
+        let timeout = Duration::from_secs(5);
+
+        server.notify::<Tick>(&42).await.unwrap();
+        for n in (0..100u32).map(|i| i * 2) {
+            assert_eq!(client.call::<Halve>(&n, timeout).await.unwrap(), n / 2);
+        }
+        assert!(matches!(
+            client.call::<Halve>(&1, timeout).await,
+            Err(RpcError::Remote(RemoteError { code: 7, .. }))
+        ));
+        let n = client.recv_notification(timeout).await.unwrap();
+        assert_eq!(n.parse::<Tick>(), Some(42));
+
+        drop(client);
+        handle.await.unwrap().unwrap();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_rpc_async_timeout() {
+        let (client_io, server_io) = tokio::io::duplex(64);
+        let (client_rx, client_tx) = tokio::io::split(client_io);
+
+        let client = AsyncClient::new(client_rx, client_tx);
+        assert!(matches!(
+            client
+                .call::<Echo>(&vec![1], Duration::from_millis(20))
+                .await,
+            Err(RpcError::Timeout)
+        ));
+        drop(server_io);
+        assert!(matches!(
+            client.recv_notification(Duration::from_secs(5)).await,
+            Err(RpcError::Closed)
+        ));
+    }
+}
@@ -0,0 +1,251 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Authenticated and encrypted COBS frames.
+//!
+//! Each payload is sealed with ChaCha20-Poly1305 under a nonce made up of a
+//! stream identifier and a per-frame sequence number, which is carried in the
+//! clear in front of the ciphertext. The receiver authenticates every frame and
+//! rejects replayed or reordered ones based on the sequence number.
+//!
+//! The same key may be used in both directions of a link, as long as the two
+//! directions use distinct stream identifiers.
+
+use crate::{DecodeError, decode, decode_buffer, encode_buffer, encode_unsafe};
+use chacha20poly1305::aead::AeadInPlace;
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce, Tag};
+use zeroize::Zeroizing;
+
+/// Size of the encryption keys in bytes.
+pub const KEY_SIZE: usize = 32;
+
+/// Number of bytes a sealed frame's payload grows by before stuffing: the
+/// sequence number and the authentication tag.
+pub const OVERHEAD: usize = SEQ_SIZE + TAG_SIZE;
+
+const SEQ_SIZE: usize = 8;
+const TAG_SIZE: usize = 16;
+
+/// Number of sequence numbers below the highest seen one that are tracked for
+/// distinguishing replayed frames from late ones.
+const WINDOW: u64 = 64;
+
+/// Error types that can be returned from sealing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SealError {
+    #[error("sequence numbers exhausted")]
+    Exhausted,
+}
+
+/// Error types that can be returned from opening.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum OpenError {
+    #[error("decode failed: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("frame too short: have {have} bytes, want at least {want} bytes")]
+    Truncated { have: usize, want: usize },
+    #[error("authentication failed")]
+    Tampered,
+    #[error("replayed frame: sequence {seq}, highest seen {highest}")]
+    Replayed { seq: u64, highest: u64 },
+    #[error("reordered frame: sequence {seq}, highest seen {highest}")]
+    Reordered { seq: u64, highest: u64 },
+}
+
+// Assembles the nonce of a frame from its stream and sequence number
+fn nonce(stream: u32, seq: u64) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[..4].copy_from_slice(&stream.to_be_bytes());
+    nonce[4..].copy_from_slice(&seq.to_be_bytes());
+    nonce
+}
+
+/// Sending half of a secure link, sealing payloads into COBS frames.
+pub struct Sealer {
+    cipher: ChaCha20Poly1305,
+    stream: u32,
+    seq: u64,
+}
+
+impl Sealer {
+    /// Creates a sealer for the given key and stream identifier.
+    pub fn new(key: &[u8; KEY_SIZE], stream: u32) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(key.into()),
+            stream,
+            seq: 0,
+        }
+    }
+
+    /// Encrypts and authenticates a payload, returning the COBS encoded frame
+    /// (without a trailing delimiter).
+    pub fn seal(&mut self, payload: &[u8]) -> Result<Vec<u8>, SealError> {
+        if self.seq == u64::MAX {
+            return Err(SealError::Exhausted);
+        }
+        let seq = self.seq;
+        self.seq += 1;
+
+        // Assemble the frame in a scratch buffer wiped after use, encrypting
+        // the payload in place
+        let mut frame = Zeroizing::new(Vec::with_capacity(payload.len() + OVERHEAD));
+        frame.extend_from_slice(&seq.to_be_bytes());
+        frame.extend_from_slice(payload);
+
+        let (header, body) = frame.split_at_mut(SEQ_SIZE);
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(&nonce(self.stream, seq), header, body)
+            .expect("payload within cipher limits");
+        frame.extend_from_slice(&tag);
+
+        let mut encoded = vec![0u8; encode_buffer(frame.len())];
+        let len = encode_unsafe(&frame, &mut encoded);
+        encoded.truncate(len);
+        Ok(encoded)
+    }
+}
+
+/// Receiving half of a secure link, opening COBS frames produced by a [`Sealer`].
+pub struct Opener {
+    cipher: ChaCha20Poly1305,
+    stream: u32,
+    highest: Option<u64>, // Highest sequence number accepted so far
+    seen: u64,            // Bitmap of accepted sequence numbers below the highest
+}
+
+impl Opener {
+    /// Creates an opener for the given key and stream identifier.
+    pub fn new(key: &[u8; KEY_SIZE], stream: u32) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(key.into()),
+            stream,
+            highest: None,
+            seen: 0,
+        }
+    }
+
+    /// Decodes, authenticates and decrypts a COBS frame (without the trailing
+    /// delimiter). Frames may be lost, but they must not be replayed or arrive
+    /// out of order. Rejected frames do not affect the opener's state.
+    pub fn open(&mut self, frame: &[u8]) -> Result<Zeroizing<Vec<u8>>, OpenError> {
+        let mut decoded = Zeroizing::new(vec![0u8; decode_buffer(frame.len())]);
+        let len = decode(frame, &mut decoded)?;
+        if len < OVERHEAD {
+            return Err(OpenError::Truncated {
+                have: len,
+                want: OVERHEAD,
+            });
+        }
+        decoded.truncate(len);
+
+        // Authenticate and decrypt the frame before trusting anything in it
+        let (header, rest) = decoded.split_at_mut(SEQ_SIZE);
+        let (body, tag) = rest.split_at_mut(rest.len() - TAG_SIZE);
+        let seq = u64::from_be_bytes(header.try_into().unwrap());
+
+        self.cipher
+            .decrypt_in_place_detached(&nonce(self.stream, seq), header, body, Tag::from_slice(tag))
+            .map_err(|_| OpenError::Tampered)?;
+
+        // Ensure the frame is fresh, tracking recent ones to tell replays apart
+        match self.highest {
+            Some(highest) if seq <= highest => {
+                let age = highest - seq;
+                if age == 0 || age > WINDOW || self.seen & (1 << (age - 1)) != 0 {
+                    return Err(OpenError::Replayed { seq, highest });
+                }
+                return Err(OpenError::Reordered { seq, highest });
+            }
+            Some(highest) => {
+                let shift = seq - highest;
+                self.seen = if shift > WINDOW {
+                    0
+                } else {
+                    ((self.seen << 1) | 1) << (shift - 1)
+                };
+            }
+            None => {}
+        }
+        self.highest = Some(seq);
+
+        decoded.copy_within(SEQ_SIZE..len - TAG_SIZE, 0);
+        decoded.truncate(len - OVERHEAD);
+        Ok(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; KEY_SIZE] = [0x42; KEY_SIZE];
+
+    // Splits an in-memory pipe into its delimited frames
+    fn frames(pipe: &[u8]) -> Vec<&[u8]> {
+        pipe.split(|&b| b == 0).filter(|f| !f.is_empty()).collect()
+    }
+
+    #[test]
+    fn test_seal_roundtrip_over_pipe() {
+        let mut sealer = Sealer::new(&KEY, 1);
+        let mut opener = Opener::new(&KEY, 1);
+
+        let payloads: Vec<Vec<u8>> = (0..50).map(|i| vec![i as u8 % 3; i * 11]).collect();
+        let mut pipe = Vec::new();
+        for payload in &payloads {
+            pipe.extend(sealer.seal(payload).unwrap());
+            pipe.push(0);
+        }
+        for (frame, payload) in frames(&pipe).into_iter().zip(&payloads) {
+            assert_eq!(*opener.open(frame).unwrap(), *payload);
+        }
+    }
+
+    #[test]
+    fn test_seal_rejects_tampering() {
+        let mut sealer = Sealer::new(&KEY, 1);
+        let frame = sealer.seal(b"sensitive device data").unwrap();
+
+        // Flipping any byte must fail authentication or framing
+        for i in 0..frame.len() {
+            let mut bad = frame.clone();
+            bad[i] ^= 0x01;
+            let mut opener = Opener::new(&KEY, 1);
+            assert!(opener.open(&bad).is_err(), "tampered byte {i} accepted");
+        }
+        // Frames from another stream or key are rejected too
+        assert_eq!(
+            Opener::new(&KEY, 2).open(&frame).unwrap_err(),
+            OpenError::Tampered
+        );
+        assert_eq!(
+            Opener::new(&[7; KEY_SIZE], 1).open(&frame).unwrap_err(),
+            OpenError::Tampered
+        );
+    }
+
+    #[test]
+    fn test_seal_rejects_replay_and_reorder() {
+        let mut sealer = Sealer::new(&KEY, 1);
+        let frames: Vec<Vec<u8>> = (0..5).map(|i| sealer.seal(&[i]).unwrap()).collect();
+
+        let mut opener = Opener::new(&KEY, 1);
+        assert_eq!(*opener.open(&frames[0]).unwrap(), [0]);
+        assert_eq!(
+            opener.open(&frames[0]).unwrap_err(),
+            OpenError::Replayed { seq: 0, highest: 0 }
+        );
+        // Losing frames is fine, but the skipped ones can't arrive late
+        assert_eq!(*opener.open(&frames[3]).unwrap(), [3]);
+        assert_eq!(
+            opener.open(&frames[1]).unwrap_err(),
+            OpenError::Reordered { seq: 1, highest: 3 }
+        );
+        assert_eq!(
+            opener.open(&frames[0]).unwrap_err(),
+            OpenError::Replayed { seq: 0, highest: 3 }
+        );
+        assert_eq!(*opener.open(&frames[4]).unwrap(), [4]);
+    }
+}
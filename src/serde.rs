@@ -0,0 +1,310 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Typed messages over COBS frames, serialized with [`postcard`].
+//!
+//! Values are serialized straight into the COBS stuffer, so the unstuffed form
+//! of a message is never buffered, and deserialized from decoded frames. The
+//! sending side always terminates the frame with a 0x00 delimiter.
+
+use crate::DecodeError;
+use crate::stuffer::Stuffer;
+use ::serde::{Deserialize, Serialize};
+use postcard::ser_flavors::Flavor;
+
+#[cfg(feature = "std")]
+use {
+    crate::io::FrameReader,
+    crate::stuffer::ChunkStuffer,
+    ::serde::de::DeserializeOwned,
+    std::io::{self, Read, Write},
+};
+
+#[cfg(feature = "tokio")]
+use {
+    crate::codec::AsyncFrameReader,
+    tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+};
+
+/// Error types that can be returned from typed messaging.
+#[derive(Debug, thiserror::Error)]
+pub enum SerdeError {
+    #[error("buffer too small")]
+    BufferTooSmall,
+    #[error("postcard: {0}")]
+    Postcard(postcard::Error),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl From<postcard::Error> for SerdeError {
+    fn from(err: postcard::Error) -> Self {
+        match err {
+            postcard::Error::SerializeBufferFull => SerdeError::BufferTooSmall,
+            err => SerdeError::Postcard(err),
+        }
+    }
+}
+
+/// Postcard sink stuffing into a fixed buffer.
+struct SliceFlavor<'a> {
+    stuffer: Stuffer<&'a mut [u8]>,
+}
+
+impl Flavor for SliceFlavor<'_> {
+    type Output = usize;
+
+    #[inline]
+    fn try_push(&mut self, b: u8) -> postcard::Result<()> {
+        match self.stuffer.try_push(b) {
+            true => Ok(()),
+            false => Err(postcard::Error::SerializeBufferFull),
+        }
+    }
+
+    #[inline]
+    fn finalize(mut self) -> postcard::Result<usize> {
+        Ok(self.stuffer.finish())
+    }
+}
+
+/// Serializes a value into a 0x00 delimited COBS frame in a fixed buffer.
+/// Returns the length of the frame, delimiter included. Returns an error if the
+/// buffer is too small or if the value cannot be serialized.
+pub fn to_slice<T: Serialize + ?Sized>(value: &T, encoded: &mut [u8]) -> Result<usize, SerdeError> {
+    // Reserve room for the delimiter (and for the empty encoding)
+    let Some(room) = encoded.len().checked_sub(1).filter(|&n| n > 0) else {
+        return Err(SerdeError::BufferTooSmall);
+    };
+    let flavor = SliceFlavor {
+        stuffer: Stuffer::new(&mut encoded[..room]),
+    };
+    let len = postcard::serialize_with_flavor(value, flavor)?;
+    encoded[len] = 0;
+    Ok(len + 1)
+}
+
+/// Deserializes a value from a decoded frame payload, such as the ones yielded
+/// by a [`FrameBuffer`](crate::FrameBuffer).
+pub fn from_slice<'a, T: Deserialize<'a>>(payload: &'a [u8]) -> Result<T, SerdeError> {
+    Ok(postcard::from_bytes(payload)?)
+}
+
+/// Postcard sink stuffing into a byte stream, one chunk at a time.
+#[cfg(feature = "std")]
+struct WriteFlavor<'a, W: Write> {
+    writer: &'a mut W,
+    stuffer: ChunkStuffer,
+    error: &'a mut Option<io::Error>, // Failure of the stream, lost otherwise
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> Flavor for WriteFlavor<'_, W> {
+    type Output = ();
+
+    #[inline]
+    fn try_push(&mut self, b: u8) -> postcard::Result<()> {
+        match self.stuffer.push(b) {
+            Some(chunk) => write_chunk(self.writer, chunk, self.error),
+            None => Ok(()),
+        }
+    }
+
+    fn finalize(mut self) -> postcard::Result<()> {
+        write_chunk(self.writer, self.stuffer.finish(), self.error)?;
+        write_chunk(self.writer, &[0], self.error)
+    }
+}
+
+// Writes a chunk into the stream, stashing away any failure
+#[cfg(feature = "std")]
+fn write_chunk<W: Write>(
+    writer: &mut W,
+    chunk: &[u8],
+    error: &mut Option<io::Error>,
+) -> postcard::Result<()> {
+    writer.write_all(chunk).map_err(|err| {
+        *error = Some(err);
+        postcard::Error::SerializeBufferFull
+    })
+}
+
+/// Serializes a value into a freshly allocated 0x00 delimited COBS frame.
+#[cfg(feature = "std")]
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, SerdeError> {
+    let mut encoded = Vec::new();
+    send(&mut encoded, value)?;
+    Ok(encoded)
+}
+
+/// Serializes a value and writes it into a stream as a 0x00 delimited COBS
+/// frame. Chunks are written as soon as they are stuffed, so wrap the stream
+/// in a [`BufWriter`](std::io::BufWriter) if small writes are costly.
+#[cfg(feature = "std")]
+pub fn send<T: Serialize + ?Sized, W: Write>(writer: &mut W, value: &T) -> Result<(), SerdeError> {
+    let mut error = None;
+    let flavor = WriteFlavor {
+        writer,
+        stuffer: ChunkStuffer::new(),
+        error: &mut error,
+    };
+    match postcard::serialize_with_flavor(value, flavor) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(error.map_or_else(|| err.into(), SerdeError::Io)),
+    }
+}
+
+/// Reads the next frame from a frame reader and deserializes it. Returns `None`
+/// if the stream ended cleanly on a frame boundary.
+#[cfg(feature = "std")]
+pub fn recv<T: DeserializeOwned, R: Read>(
+    reader: &mut FrameReader<R>,
+) -> Result<Option<T>, SerdeError> {
+    match reader.read_frame() {
+        Ok(Some(frame)) => Ok(Some(from_slice(&frame)?)),
+        Ok(None) => Ok(None),
+        Err(err) => Err(unwrap_io(err)),
+    }
+}
+
+/// Serializes a value and writes it into an async stream as a 0x00 delimited
+/// COBS frame.
+#[cfg(feature = "tokio")]
+pub async fn send_async<T: Serialize + ?Sized, W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    value: &T,
+) -> Result<(), SerdeError> {
+    writer.write_all(&to_vec(value)?).await?;
+    Ok(())
+}
+
+/// Reads the next frame from an async frame reader and deserializes it. Returns
+/// `None` if the stream ended cleanly on a frame boundary.
+#[cfg(feature = "tokio")]
+pub async fn recv_async<T: DeserializeOwned, R: AsyncRead + Unpin>(
+    reader: &mut AsyncFrameReader<R>,
+) -> Result<Option<T>, SerdeError> {
+    match reader.read_frame().await {
+        Ok(Some(frame)) => Ok(Some(from_slice(&frame)?)),
+        Ok(None) => Ok(None),
+        Err(err) => Err(unwrap_io(err)),
+    }
+}
+
+// Converts an I/O error into a serde error, unwrapping the decoding failures
+// that the frame readers wrap into I/O errors
+#[cfg(feature = "std")]
+fn unwrap_io(err: io::Error) -> SerdeError {
+    match err.get_ref().and_then(|e| e.downcast_ref::<DecodeError>()) {
+        Some(&err) => SerdeError::Decode(err),
+        None => SerdeError::Io(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FrameBuffer, encode, encode_buffer};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: u16,
+        values: Vec<i32>,
+        label: String,
+    }
+
+    // Creates a message of a given number of values
+    fn reading(n: usize) -> Reading {
+        Reading {
+            sensor: 0x0100,
+            values: (0..n as i32).map(|i| i * 1000 - 7).collect(),
+            label: "probe\0zero".into(),
+        }
+    }
+
+    #[test]
+    fn test_to_slice_matches_encode() {
+        for n in [0, 1, 10, 100, 1000] {
+            let msg = reading(n);
+
+            // Stuffing while serializing must match serializing, then stuffing
+            let mut plain = vec![0u8; 8 * n + 64];
+            let plain = postcard::to_slice(&msg, &mut plain).unwrap();
+            let mut want = vec![0u8; encode_buffer(plain.len()) + 1];
+            let len = encode(plain, &mut want).unwrap();
+            want.truncate(len + 1);
+
+            let mut have = vec![0u8; want.len()];
+            assert_eq!(to_slice(&msg, &mut have).unwrap(), want.len());
+            assert_eq!(have, want);
+
+            // Any smaller buffer must be rejected without panicking
+            for size in 0..want.len() {
+                assert!(matches!(
+                    to_slice(&msg, &mut have[..size]),
+                    Err(SerdeError::BufferTooSmall)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_frame_buffer_roundtrip() {
+        let mut encoded = [0u8; 512];
+        let len = to_slice(&reading(20), &mut encoded).unwrap();
+
+        let mut frames = FrameBuffer::<256>::new();
+        let (_, frame) = frames.push_slice(&encoded[..len]);
+        let msg: Reading = from_slice(frame.unwrap().unwrap()).unwrap();
+        assert_eq!(msg, reading(20));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_send_recv_blocking() {
+        let mut stream = Vec::new();
+        for n in 0..50 {
+            send(&mut stream, &reading(n)).unwrap();
+        }
+        let mut encoded = [0u8; 512];
+        let len = to_slice(&reading(49), &mut encoded).unwrap();
+        assert!(stream.ends_with(&encoded[..len]));
+        assert_eq!(to_vec(&reading(49)).unwrap(), &encoded[..len]);
+
+        stream.extend_from_slice(&[0x03, 0x01, 0x00]);
+
+        let mut reader = FrameReader::new(&stream[..]);
+        for n in 0..50 {
+            assert_eq!(recv::<Reading, _>(&mut reader).unwrap(), Some(reading(n)));
+        }
+        assert!(matches!(
+            recv::<Reading, _>(&mut reader),
+            Err(SerdeError::Decode(DecodeError::ChunkOverflow { .. }))
+        ));
+        assert!(matches!(recv::<Reading, _>(&mut reader), Ok(None)));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_send_recv_async() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let sender = tokio::spawn(async move {
+            for n in 0..50 {
+                send_async(&mut client, &reading(n)).await.unwrap();
+            }
+        });
+        let mut reader = AsyncFrameReader::new(server);
+        for n in 0..50 {
+            let msg = recv_async::<Reading, _>(&mut reader).await.unwrap();
+            assert_eq!(msg, Some(reading(n)));
+        }
+        sender.await.unwrap();
+        assert!(matches!(
+            recv_async::<Reading, _>(&mut reader).await,
+            Ok(None)
+        ));
+    }
+}
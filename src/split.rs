@@ -0,0 +1,142 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+use crate::DecodeError;
+use core::ops::Range;
+
+/// Decodes an opaque data blob with COBS using 0 as the sentinel value, where
+/// the encoded frame is split across two slices (e.g. wrapping around the end
+/// of a circular buffer). Returns the number of bytes the decoding took. Returns
+/// an error if the output buffer is too small or if the input is malformed.
+///
+/// The result is identical to calling [`decode`](crate::decode) on the two
+/// slices concatenated, error positions included.
+pub fn decode_split(first: &[u8], second: &[u8], decoded: &mut [u8]) -> Result<usize, DecodeError> {
+    let len = first.len() + second.len();
+    if len == 0 {
+        return Err(DecodeError::EmptyInput);
+    }
+    if len > 1 {
+        let want = len - 1;
+        if decoded.len() < want {
+            return Err(DecodeError::BufferTooSmall {
+                have: decoded.len(),
+                want,
+            });
+        }
+    }
+    // Consume the input stream one chunk at a time, crossing the wrap point
+    // wherever it may fall
+    let input = Split { first, second };
+
+    let mut output_pos = 0usize;
+    let mut i = 0usize;
+
+    while i < len {
+        // Zero cannot be part of a COBS encoded stream
+        let marker = input.at(i);
+        if marker == 0 {
+            return Err(DecodeError::ZeroMarker { at: i });
+        }
+        // If the marker defines an overflowing chunk, abort
+        if i + marker as usize > len {
+            return Err(DecodeError::ChunkOverflow { at: i, marker, len });
+        }
+        // Consume the entire chunk, ensuring there's no zero in it
+        let mut start = i + 1;
+        for run in input.slices(start..i + marker as usize) {
+            if let Some(at) = run.iter().position(|&b| b == 0) {
+                return Err(DecodeError::ZeroBinary { at: start + at });
+            }
+            decoded[output_pos..output_pos + run.len()].copy_from_slice(run);
+            output_pos += run.len();
+            start += run.len();
+        }
+        i += marker as usize;
+
+        // If we had a partial chunk, there must be a zero following
+        if i < len && marker != 0xff {
+            decoded[output_pos] = 0;
+            output_pos += 1;
+        }
+    }
+    Ok(output_pos)
+}
+
+/// Logical concatenation of two byte slices.
+struct Split<'a> {
+    first: &'a [u8],
+    second: &'a [u8],
+}
+
+impl<'a> Split<'a> {
+    // Retrieves the byte at the given logical position
+    #[inline]
+    fn at(&self, i: usize) -> u8 {
+        match self.first.get(i) {
+            Some(&b) => b,
+            None => self.second[i - self.first.len()],
+        }
+    }
+
+    // Retrieves the given logical range as (up to) two physical slices
+    #[inline]
+    fn slices(&self, range: Range<usize>) -> [&'a [u8]; 2] {
+        let split = self.first.len();
+        if range.end <= split {
+            [&self.first[range], &[]]
+        } else if range.start >= split {
+            [&self.second[range.start - split..range.end - split], &[]]
+        } else {
+            [
+                &self.first[range.start..],
+                &self.second[..range.end - split],
+            ]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, decode_buffer, encode, encode_buffer};
+    use rand::Rng;
+
+    #[test]
+    fn test_decode_split_matches_decode() {
+        let mut rng = rand::rng();
+        for size in [0, 1, 5, 254, 255, 600] {
+            let data: Vec<u8> = (0..size).map(|_| rng.random_range(0..4)).collect();
+            let mut enc = vec![0u8; encode_buffer(size)];
+            let len = encode(&data, &mut enc).unwrap();
+
+            for split in 0..=len {
+                let mut dec = vec![0u8; decode_buffer(len)];
+                let n = decode_split(&enc[..split], &enc[split..len], &mut dec).unwrap();
+                assert_eq!(&dec[..n], &data[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_split_errors() {
+        let inputs: [&[u8]; 6] = [
+            &[],
+            &[0x00],
+            &[0x03, 0x01],
+            &[0x02, 0x00, 0x01],
+            &[0x04, 0x01, 0x01, 0x00],
+            &[0x01, 0x01, 0x00],
+        ];
+        for input in inputs {
+            let mut want = vec![0u8; decode_buffer(input.len())];
+            let want = decode(input, &mut want).map(|n| want[..n].to_vec());
+            for split in 0..=input.len() {
+                let mut have = vec![0u8; decode_buffer(input.len())];
+                let have = decode_split(&input[..split], &input[split..], &mut have)
+                    .map(|n| have[..n].to_vec());
+                assert_eq!(have, want, "input {:?}, split {}", input, split);
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+/// Incremental COBS encoder writing into a pre-sized output buffer, for layers
+/// that need to stuff bytes as they are produced rather than from one slice.
+///
+/// The caller must ensure the output has at least `encode_buffer(n)` bytes for
+/// `n` pushed bytes, otherwise pushing panics.
+pub(crate) struct Stuffer<B: AsMut<[u8]>> {
+    encoded: B,
+    marker_pos: usize,
+    output_pos: usize,
+    run_length: u8,
+    last_byte: Option<u8>,
+}
+
+impl<B: AsMut<[u8]>> Stuffer<B> {
+    /// Creates a stuffer writing into the given output buffer.
+    #[inline]
+    pub(crate) fn new(encoded: B) -> Self {
+        Self {
+            encoded,
+            marker_pos: 0,
+            output_pos: 1,
+            run_length: 1,
+            last_byte: None,
+        }
+    }
+
+    /// Appends a byte to the encoding.
+    #[inline]
+    pub(crate) fn push(&mut self, b: u8) {
+        self.last_byte = Some(b);
+
+        // If the next byte is non-zero, append it to the output
+        if b > 0 {
+            self.encoded.as_mut()[self.output_pos] = b;
+            self.output_pos += 1;
+            self.run_length += 1;
+
+            // If an entire chunk was non-zero, mark and start the next chunk
+            if self.run_length == 0xff {
+                self.close();
+            }
+        } else {
+            // Next byte is zero, terminate the chunk and start the next chunk
+            self.close();
+        }
+    }
+
+    /// Appends a byte to the encoding if the output has room for it (such that
+    /// the encoding can still be finished), returning whether it did.
+    #[cfg(feature = "serde")]
+    #[inline]
+    pub(crate) fn try_push(&mut self, b: u8) -> bool {
+        if self.output_pos >= self.encoded.as_mut().len() {
+            return false;
+        }
+        self.push(b);
+        true
+    }
+
+    /// Appends a slice of bytes to the encoding.
+    #[inline]
+    pub(crate) fn extend(&mut self, data: &[u8]) {
+        for &b in data {
+            self.push(b);
+        }
+    }
+
+    /// Terminates the encoding, returning the number of bytes written.
+    #[inline]
+    pub(crate) fn finish(&mut self) -> usize {
+        match self.last_byte {
+            // The empty blob is always encoded as 0x01
+            None => {
+                self.encoded.as_mut()[0] = 0x01;
+                1
+            }
+            // Terminate any unfinished chunk
+            Some(b) if self.run_length > 1 || b == 0 => {
+                self.encoded.as_mut()[self.marker_pos] = self.run_length;
+                self.output_pos
+            }
+            // Just finished at the chunk boundary, revert last open
+            Some(_) => self.output_pos - 1,
+        }
+    }
+
+    // Marks the current chunk's length and opens the next one
+    #[inline]
+    fn close(&mut self) {
+        self.encoded.as_mut()[self.marker_pos] = self.run_length;
+        self.marker_pos = self.output_pos;
+        self.output_pos += 1;
+        self.run_length = 1;
+    }
+}
+
+/// Incremental COBS encoder emitting every chunk as soon as it's complete, for
+/// layers that stream the encoding out rather than into a pre-sized buffer.
+///
+/// It's a [`Stuffer`] over a window of a single chunk, rewound every time the
+/// chunk in it is closed.
+#[cfg(all(feature = "serde", feature = "std"))]
+pub(crate) struct ChunkStuffer {
+    stuffer: Stuffer<[u8; 255]>,
+}
+
+#[cfg(all(feature = "serde", feature = "std"))]
+impl ChunkStuffer {
+    /// Creates a stuffer with nothing emitted yet.
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self {
+            stuffer: Stuffer::new([0; 255]),
+        }
+    }
+
+    /// Appends a byte to the encoding, returning the chunk it completed, if any.
+    #[inline]
+    pub(crate) fn push(&mut self, b: u8) -> Option<&[u8]> {
+        self.stuffer.push(b);
+
+        // If the byte closed the chunk, the next one was opened right after it,
+        // hand out the closed one and move the new one to the window's start
+        let len = self.stuffer.marker_pos;
+        if len == 0 {
+            return None;
+        }
+        self.stuffer.marker_pos = 0;
+        self.stuffer.output_pos = 1;
+        Some(&self.stuffer.encoded[..len])
+    }
+
+    /// Terminates the encoding, returning the last chunk (which may be empty).
+    #[inline]
+    pub(crate) fn finish(&mut self) -> &[u8] {
+        let len = self.stuffer.finish();
+        &self.stuffer.encoded[..len]
+    }
+}
+
+#[cfg(all(test, feature = "serde", feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::testutil::random;
+    use crate::{encode, encode_buffer};
+
+    #[test]
+    fn test_chunk_stuffer_matches_encode() {
+        for size in [0, 1, 253, 254, 255, 508, 1000] {
+            for zeros in [0.0, 0.01, 0.5] {
+                let data = random(size, zeros);
+
+                let mut want = vec![0u8; encode_buffer(size)];
+                let len = encode(&data, &mut want).unwrap();
+                want.truncate(len);
+
+                let mut stuffer = ChunkStuffer::new();
+                let mut have = Vec::new();
+                for &b in &data {
+                    if let Some(chunk) = stuffer.push(b) {
+                        have.extend_from_slice(chunk);
+                    }
+                }
+                have.extend_from_slice(stuffer.finish());
+                assert_eq!(have, want, "size {size}, zeros {zeros}");
+            }
+        }
+    }
+}
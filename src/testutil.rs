@@ -0,0 +1,20 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Fixtures shared by the test modules of the crate.
+
+use rand::Rng;
+
+// Generates a random blob with the given probability of zero bytes
+pub fn random(size: usize, zeros: f64) -> Vec<u8> {
+    let mut rng = rand::rng();
+    (0..size)
+        .map(|_| {
+            if rng.random_bool(zeros) {
+                0
+            } else {
+                rng.random_range(1..=255)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,938 @@
+// cobs-rs: fast cobs encoder and decoder
+// Copyright 2025 Dark Bio AG. All rights reserved.
+
+//! Resumable transfer of firmware images and other blobs over COBS links.
+//!
+//! A [`Sender`] offers an image (name, size and SHA-256 hash) to a [`Receiver`],
+//! which accepts it from the offset it already holds, or rejects it. The image
+//! then flows in chunks sized to the smaller of the two ends' MTUs, a window of
+//! them in flight, acknowledged cumulatively and resent from the last
+//! acknowledged offset on timeout. The receiver verifies the hash of the whole
+//! image before confirming completion. Either end can abort at any time.
+//!
+//! Like the [`arq`](crate::arq) link, both ends are sans-IO: the caller moves
+//! frames between `poll_transmit` and `receive`, and time comes from a
+//! [`Clock`]. Every frame carries a CRC-32C, so corrupted ones are dropped and
+//! recovered by retransmission.
+//!
+//! Wire format of the frames before the checksum and stuffing, all integers
+//! big endian:
+//! - Offer: `0x10`, id (u32), size (u64), MTU (u16), hash (32 bytes), name
+//! - Accept: `0x11`, id, offset (u64), MTU (u16)
+//! - Data: `0x12`, id, offset (u64), bytes
+//! - Ack: `0x13`, id, next offset (u64)
+//! - Done: `0x14`, id
+//! - Abort: `0x15`, id, reason (u8)
+
+use crate::arq::{Clock, SystemClock, seal};
+use crate::crc::{Crc32, decode_checked};
+use crate::decode_buffer;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// Type bytes of the frames
+const KIND_OFFER: u8 = 0x10;
+const KIND_ACCEPT: u8 = 0x11;
+const KIND_DATA: u8 = 0x12;
+const KIND_ACK: u8 = 0x13;
+const KIND_DONE: u8 = 0x14;
+const KIND_ABORT: u8 = 0x15;
+
+/// Tunables of a transfer end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferConfig {
+    /// Largest chunk of image data this end handles in a frame. The transfer
+    /// runs at the smaller of the two ends' values.
+    pub mtu: u16,
+    /// Number of chunks in flight without acknowledgement (sender only).
+    pub window: u16,
+    /// Time to wait for progress before resending (sender only).
+    pub timeout: Duration,
+    /// Number of consecutive resends without progress before giving up
+    /// (sender only).
+    pub max_retries: u32,
+}
+
+impl Default for TransferConfig {
+    fn default() -> Self {
+        Self {
+            mtu: 256,
+            window: 8,
+            timeout: Duration::from_millis(500),
+            max_retries: 10,
+        }
+    }
+}
+
+/// Reason a transfer was aborted, as carried on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbortReason {
+    /// The transfer was cancelled by the application.
+    Cancelled,
+    /// The receiver turned the offer down.
+    Rejected,
+    /// The received image didn't match the offered hash.
+    HashMismatch,
+    /// Some reason not known to this end.
+    Other(u8),
+}
+
+impl AbortReason {
+    // Converts the reason into its wire code
+    fn code(self) -> u8 {
+        match self {
+            AbortReason::Cancelled => 1,
+            AbortReason::Rejected => 2,
+            AbortReason::HashMismatch => 3,
+            AbortReason::Other(code) => code,
+        }
+    }
+
+    // Converts a wire code into a reason
+    fn from_code(code: u8) -> Self {
+        match code {
+            1 => AbortReason::Cancelled,
+            2 => AbortReason::Rejected,
+            3 => AbortReason::HashMismatch,
+            code => AbortReason::Other(code),
+        }
+    }
+}
+
+/// Error types that can be returned from a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum TransferError {
+    #[error("no progress after {retries} resends")]
+    RetriesExhausted { retries: u32 },
+    #[error("transfer aborted: {reason:?} (remote: {remote})")]
+    Aborted { reason: AbortReason, remote: bool },
+}
+
+/// Progress of a [`Sender`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SenderState {
+    /// Waiting for the receiver to accept the offer.
+    Offering,
+    /// Sending the image, with the given number of bytes acknowledged.
+    Sending { acked: u64 },
+    /// The receiver confirmed the complete, verified image.
+    Complete,
+    /// The transfer failed or was aborted.
+    Failed(TransferError),
+}
+
+/// Sans-IO sending end of a transfer.
+#[derive(Debug)]
+pub struct Sender<C: Clock = SystemClock> {
+    config: TransferConfig,
+    clock: C,
+    id: u32,
+    name: String,
+    image: Vec<u8>,
+    hash: [u8; 32],
+
+    state: SenderState,
+    mtu: usize,              // Negotiated chunk size
+    next: u64,               // Offset of the next chunk to send
+    offered: bool,           // Whether the offer went out at least once
+    deadline: Duration,      // Time at which to resend if there's no progress
+    retries: u32,            // Resends since the last progress
+    outbox: Option<Vec<u8>>, // Abort frame waiting to go out
+    sent: u64,               // Image bytes put on the wire, resends included
+}
+
+impl<C: Clock> Sender<C> {
+    /// Creates the sending end of a transfer of an image. The id tells apart
+    /// transfers, so stale frames of an earlier one are not mistaken for ours.
+    pub fn new(config: TransferConfig, clock: C, id: u32, name: &str, image: Vec<u8>) -> Self {
+        let hash = Sha256::digest(&image).into();
+        Self {
+            config,
+            clock,
+            id,
+            name: name.to_string(),
+            image,
+            hash,
+            state: SenderState::Offering,
+            mtu: config.mtu.max(1) as usize,
+            next: 0,
+            offered: false,
+            deadline: Duration::ZERO,
+            retries: 0,
+            outbox: None,
+            sent: 0,
+        }
+    }
+
+    /// Returns the progress of the transfer.
+    pub fn state(&self) -> SenderState {
+        self.state
+    }
+
+    /// Returns the number of image bytes put on the wire so far, resends
+    /// included.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Aborts the transfer, notifying the receiver.
+    pub fn abort(&mut self) {
+        if matches!(self.state, SenderState::Complete | SenderState::Failed(_)) {
+            return;
+        }
+        self.outbox = Some(abort_frame(self.id, AbortReason::Cancelled));
+        self.state = SenderState::Failed(TransferError::Aborted {
+            reason: AbortReason::Cancelled,
+            remote: false,
+        });
+    }
+
+    /// Returns the next encoded frame to put on the wire (without the 0x00
+    /// delimiter), or `None` if there's nothing to send right now.
+    ///
+    /// Returns an error once the transfer failed, after which it's dead.
+    pub fn poll_transmit(&mut self) -> Result<Option<Vec<u8>>, TransferError> {
+        if let Some(frame) = self.outbox.take() {
+            return Ok(Some(frame));
+        }
+        let now = self.clock.now();
+        match self.state {
+            SenderState::Offering => {
+                if now < self.deadline {
+                    return Ok(None);
+                }
+                self.resend(now)?;
+
+                let mut body = header(KIND_OFFER, self.id);
+                body.extend_from_slice(&(self.image.len() as u64).to_be_bytes());
+                body.extend_from_slice(&self.config.mtu.to_be_bytes());
+                body.extend_from_slice(&self.hash);
+                body.extend_from_slice(self.name.as_bytes());
+                Ok(Some(seal(&body)))
+            }
+            SenderState::Sending { acked } => {
+                let size = self.image.len() as u64;
+                let window = self.config.window.max(1) as u64 * self.mtu as u64;
+                if self.next < size && self.next - acked < window {
+                    return Ok(Some(self.chunk(now)));
+                }
+                if now < self.deadline {
+                    return Ok(None);
+                }
+                // No progress in time, go back to the last acknowledged offset.
+                // If everything was acknowledged but the completion got lost,
+                // poke the receiver with an empty chunk to hear it again.
+                self.resend(now)?;
+                self.next = acked;
+                Ok(Some(self.chunk(now)))
+            }
+            SenderState::Complete => Ok(None),
+            SenderState::Failed(err) => Err(err),
+        }
+    }
+
+    /// Returns the earliest time (on the sender's clock) at which a resend
+    /// becomes due, or `None` if the transfer is over.
+    pub fn next_timeout(&self) -> Option<Duration> {
+        match self.state {
+            SenderState::Offering | SenderState::Sending { .. } => Some(self.deadline),
+            _ => None,
+        }
+    }
+
+    /// Processes an encoded frame (without the 0x00 delimiter) arriving from the
+    /// receiver. Corrupted, malformed or unrelated frames are silently dropped.
+    pub fn receive(&mut self, frame: &[u8]) {
+        let Some((kind, id, body)) = open(frame) else {
+            return;
+        };
+        if id != self.id || matches!(self.state, SenderState::Complete | SenderState::Failed(_)) {
+            return;
+        }
+        let size = self.image.len() as u64;
+        match (kind, body.as_slice()) {
+            (KIND_ACCEPT, [o @ .., m0, m1]) if o.len() == 8 => {
+                if self.state != SenderState::Offering {
+                    return;
+                }
+                let offset = u64::from_be_bytes(o.try_into().unwrap()).min(size);
+                let mtu = u16::from_be_bytes([*m0, *m1]).min(self.config.mtu).max(1);
+                self.mtu = mtu as usize;
+                self.next = offset;
+                self.progress(offset);
+            }
+            (KIND_ACK, next) if next.len() == 8 => {
+                let SenderState::Sending { acked } = self.state else {
+                    return;
+                };
+                let next = u64::from_be_bytes(next.try_into().unwrap());
+                if next > acked && next <= size {
+                    self.next = self.next.max(next);
+                    self.progress(next);
+                }
+            }
+            (KIND_DONE, []) => {
+                // The receiver only confirms verified images, so this is final
+                // even while offering: a receiver resuming from a complete
+                // partial may have had its accept lost on the way
+                self.state = SenderState::Complete;
+            }
+            (KIND_ABORT, [reason]) => {
+                self.state = SenderState::Failed(TransferError::Aborted {
+                    reason: AbortReason::from_code(*reason),
+                    remote: true,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // Records acknowledged progress, resetting the resend timer
+    fn progress(&mut self, acked: u64) {
+        self.state = SenderState::Sending { acked };
+        self.retries = 0;
+        self.deadline = self.clock.now() + self.config.timeout;
+    }
+
+    // Accounts for a resend, failing the transfer if out of retries. The very
+    // first offer goes through here too, so it doesn't count.
+    fn resend(&mut self, now: Duration) -> Result<(), TransferError> {
+        if self.offered {
+            if self.retries == self.config.max_retries {
+                let err = TransferError::RetriesExhausted {
+                    retries: self.retries,
+                };
+                self.state = SenderState::Failed(err);
+                return Err(err);
+            }
+            self.retries += 1;
+        }
+        self.offered = true;
+        self.deadline = now + self.config.timeout;
+        Ok(())
+    }
+
+    // Builds the data frame at the next offset and moves past it
+    fn chunk(&mut self, now: Duration) -> Vec<u8> {
+        let start = self.next as usize;
+        let end = (start + self.mtu).min(self.image.len());
+
+        let mut body = header(KIND_DATA, self.id);
+        body.extend_from_slice(&self.next.to_be_bytes());
+        body.extend_from_slice(&self.image[start..end]);
+
+        if self.deadline <= now {
+            self.deadline = now + self.config.timeout;
+        }
+        self.next = end as u64;
+        self.sent += (end - start) as u64;
+        seal(&body)
+    }
+}
+
+/// Image offered by a [`Sender`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    pub id: u32,
+    pub name: String,
+    pub size: u64,
+    pub hash: [u8; 32],
+}
+
+/// Partially received image, kept to resume its transfer later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partial {
+    pub size: u64,
+    pub hash: [u8; 32],
+    pub data: Vec<u8>,
+}
+
+/// Happening on a [`Receiver`] the application needs to act upon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// An image was offered, to be [accepted](Receiver::accept) or
+    /// [rejected](Receiver::reject).
+    Offered(Offer),
+    /// The image was received in full and its hash verified.
+    Completed { offer: Offer, image: Vec<u8> },
+    /// The transfer was aborted, by either end.
+    Aborted { reason: AbortReason, remote: bool },
+}
+
+/// Progress of a [`Receiver`].
+#[derive(Debug)]
+enum ReceiverState {
+    Idle,
+    Offered {
+        offer: Offer,
+        mtu: u16,
+    },
+    Receiving {
+        offer: Offer,
+        mtu: u16,
+        data: Vec<u8>,
+    },
+    Complete {
+        id: u32,
+    },
+}
+
+/// Sans-IO receiving end of transfers. A receiver handles one transfer at a
+/// time, and can be reused for the next ones.
+#[derive(Debug)]
+pub struct Receiver {
+    config: TransferConfig,
+    state: ReceiverState,
+    partial: Option<Partial>, // Leftover of an interrupted transfer, to resume
+    events: VecDeque<Event>,
+    outbox: VecDeque<Vec<u8>>,
+}
+
+impl Receiver {
+    /// Creates a receiving end with nothing to resume.
+    pub fn new(config: TransferConfig) -> Self {
+        Self {
+            config,
+            state: ReceiverState::Idle,
+            partial: None,
+            events: VecDeque::new(),
+            outbox: VecDeque::new(),
+        }
+    }
+
+    /// Creates a receiving end holding the leftover of an interrupted transfer.
+    /// If the same image (by size and hash) is offered again, it's accepted
+    /// from where it was left off.
+    pub fn resume(config: TransferConfig, partial: Partial) -> Self {
+        let mut receiver = Self::new(config);
+        receiver.partial = Some(partial);
+        receiver
+    }
+
+    /// Returns the partially received image of the transfer in progress (or
+    /// of the interrupted one), for persisting and resuming it later.
+    pub fn partial(&self) -> Option<Partial> {
+        match &self.state {
+            ReceiverState::Receiving { offer, data, .. } => Some(Partial {
+                size: offer.size,
+                hash: offer.hash,
+                data: data.clone(),
+            }),
+            _ => self.partial.clone(),
+        }
+    }
+
+    /// Retrieves the next event for the application.
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    /// Accepts the pending offer, resuming it if a matching partial image is
+    /// held. Does nothing if no offer is pending.
+    pub fn accept(&mut self) {
+        let state = std::mem::replace(&mut self.state, ReceiverState::Idle);
+        let ReceiverState::Offered { offer, mtu } = state else {
+            self.state = state;
+            return;
+        };
+        let mut data = Vec::new();
+        if let Some(partial) = self.partial.take() {
+            if partial.size == offer.size && partial.hash == offer.hash {
+                data = partial.data;
+                data.truncate(offer.size as usize);
+            } else {
+                self.partial = Some(partial);
+            }
+        }
+        let mtu = mtu.min(self.config.mtu).max(1);
+        self.outbox
+            .push_back(accept_frame(offer.id, data.len() as u64, mtu));
+        self.state = ReceiverState::Receiving { offer, mtu, data };
+        self.check_complete();
+    }
+
+    /// Rejects the pending offer. Does nothing if no offer is pending.
+    pub fn reject(&mut self) {
+        if let ReceiverState::Offered { offer, .. } = &self.state {
+            self.outbox
+                .push_back(abort_frame(offer.id, AbortReason::Rejected));
+            self.state = ReceiverState::Idle;
+        }
+    }
+
+    /// Aborts the transfer in progress, notifying the sender. The data received
+    /// so far is kept for resuming.
+    pub fn abort(&mut self) {
+        if let ReceiverState::Receiving { offer, .. } = &self.state {
+            self.outbox
+                .push_back(abort_frame(offer.id, AbortReason::Cancelled));
+            self.partial = self.partial();
+            self.state = ReceiverState::Idle;
+            self.events.push_back(Event::Aborted {
+                reason: AbortReason::Cancelled,
+                remote: false,
+            });
+        }
+    }
+
+    /// Returns the next encoded frame to put on the wire (without the 0x00
+    /// delimiter), or `None` if there's nothing to send. The receiver only ever
+    /// answers the sender, so it needs no timers.
+    pub fn poll_transmit(&mut self) -> Option<Vec<u8>> {
+        self.outbox.pop_front()
+    }
+
+    /// Processes an encoded frame (without the 0x00 delimiter) arriving from the
+    /// sender. Corrupted or malformed frames are silently dropped.
+    pub fn receive(&mut self, frame: &[u8]) {
+        let Some((kind, id, body)) = open(frame) else {
+            return;
+        };
+        match (kind, body.as_slice()) {
+            (KIND_OFFER, body) if body.len() >= 42 => self.receive_offer(id, body),
+            (KIND_DATA, body) if body.len() >= 8 => {
+                let offset = u64::from_be_bytes(body[..8].try_into().unwrap());
+                self.receive_data(id, offset, &body[8..]);
+            }
+            (KIND_ABORT, [reason]) if self.current() == Some(id) => {
+                self.partial = self.partial();
+                self.state = ReceiverState::Idle;
+                self.events.push_back(Event::Aborted {
+                    reason: AbortReason::from_code(*reason),
+                    remote: true,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // Returns the id of the transfer being handled, if any
+    fn current(&self) -> Option<u32> {
+        match &self.state {
+            ReceiverState::Idle => None,
+            ReceiverState::Offered { offer, .. } | ReceiverState::Receiving { offer, .. } => {
+                Some(offer.id)
+            }
+            ReceiverState::Complete { id } => Some(*id),
+        }
+    }
+
+    // Handles an offer, answering repeats of the current one directly
+    fn receive_offer(&mut self, id: u32, body: &[u8]) {
+        match &self.state {
+            ReceiverState::Offered { offer, .. } if offer.id == id => return,
+            ReceiverState::Receiving { offer, mtu, data } if offer.id == id => {
+                // Our accept got lost, repeat it
+                let frame = accept_frame(id, data.len() as u64, *mtu);
+                self.outbox.push_back(frame);
+                return;
+            }
+            ReceiverState::Complete { id: done } if *done == id => {
+                self.outbox.push_back(seal(&header(KIND_DONE, id)));
+                return;
+            }
+            ReceiverState::Receiving { .. } => {
+                // A new transfer supersedes the current one, keep it to resume
+                self.partial = self.partial();
+            }
+            _ => {}
+        }
+        let Ok(name) = String::from_utf8(body[42..].to_vec()) else {
+            return;
+        };
+        let offer = Offer {
+            id,
+            name,
+            size: u64::from_be_bytes(body[..8].try_into().unwrap()),
+            hash: body[10..42].try_into().unwrap(),
+        };
+        let mtu = u16::from_be_bytes([body[8], body[9]]);
+        self.events.push_back(Event::Offered(offer.clone()));
+        self.state = ReceiverState::Offered { offer, mtu };
+    }
+
+    // Appends an in-order chunk to the image and acknowledges where it's at
+    fn receive_data(&mut self, id: u32, offset: u64, chunk: &[u8]) {
+        match &mut self.state {
+            ReceiverState::Receiving { offer, mtu, data } if offer.id == id => {
+                if chunk.len() > *mtu as usize {
+                    return;
+                }
+                if offset == data.len() as u64 && offset + chunk.len() as u64 <= offer.size {
+                    data.extend_from_slice(chunk);
+                }
+                let mut body = header(KIND_ACK, id);
+                body.extend_from_slice(&(data.len() as u64).to_be_bytes());
+                self.outbox.push_back(seal(&body));
+                self.check_complete();
+            }
+            ReceiverState::Complete { id: done } if *done == id => {
+                // Our completion got lost, repeat it
+                self.outbox.push_back(seal(&header(KIND_DONE, id)));
+            }
+            _ => {}
+        }
+    }
+
+    // Verifies and delivers the image if it was received in full
+    fn check_complete(&mut self) {
+        let ReceiverState::Receiving { offer, data, .. } = &self.state else {
+            return;
+        };
+        if (data.len() as u64) < offer.size {
+            return;
+        }
+        let id = offer.id;
+        let state = std::mem::replace(&mut self.state, ReceiverState::Complete { id });
+        let ReceiverState::Receiving { offer, data, .. } = state else {
+            unreachable!("checked above");
+        };
+        if <[u8; 32]>::from(Sha256::digest(&data)) != offer.hash {
+            self.state = ReceiverState::Idle;
+            self.outbox
+                .push_back(abort_frame(id, AbortReason::HashMismatch));
+            self.events.push_back(Event::Aborted {
+                reason: AbortReason::HashMismatch,
+                remote: false,
+            });
+            return;
+        }
+        self.outbox.push_back(seal(&header(KIND_DONE, id)));
+        self.events
+            .push_back(Event::Completed { offer, image: data });
+    }
+}
+
+// Starts a frame body with its type and transfer id
+fn header(kind: u8, id: u32) -> Vec<u8> {
+    let mut body = vec![kind];
+    body.extend_from_slice(&id.to_be_bytes());
+    body
+}
+
+// Builds an accept frame
+fn accept_frame(id: u32, offset: u64, mtu: u16) -> Vec<u8> {
+    let mut body = header(KIND_ACCEPT, id);
+    body.extend_from_slice(&offset.to_be_bytes());
+    body.extend_from_slice(&mtu.to_be_bytes());
+    seal(&body)
+}
+
+// Builds an abort frame
+fn abort_frame(id: u32, reason: AbortReason) -> Vec<u8> {
+    let mut body = header(KIND_ABORT, id);
+    body.push(reason.code());
+    seal(&body)
+}
+
+// Verifies and unstuffs a frame, splitting it into type, transfer id and body
+fn open(frame: &[u8]) -> Option<(u8, u32, Vec<u8>)> {
+    let mut decoded = vec![0u8; decode_buffer(frame.len())];
+    let len = decode_checked(frame, Crc32::CASTAGNOLI, &mut decoded).ok()?;
+    if len < 5 {
+        return None;
+    }
+    let kind = decoded[0];
+    let id = u32::from_be_bytes(decoded[1..5].try_into().unwrap());
+    decoded.truncate(len);
+    decoded.drain(..5);
+    Some((kind, id, decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arq::ManualClock;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // Moves all the pending frames between the two ends through a lossy channel
+    // that drops, corrupts and duplicates them
+    fn pump(
+        sender: &mut Sender<ManualClock>,
+        receiver: &mut Receiver,
+        rng: &mut StdRng,
+        loss: f64,
+    ) {
+        let mut lossy = |frame: Vec<u8>| -> Vec<Vec<u8>> {
+            let mut frame = frame;
+            if rng.random_bool(loss) {
+                return vec![];
+            }
+            if rng.random_bool(loss) {
+                let at = rng.random_range(0..frame.len());
+                frame[at] ^= rng.random_range(1..=255);
+            }
+            if rng.random_bool(loss) {
+                return vec![frame.clone(), frame];
+            }
+            vec![frame]
+        };
+        while let Ok(Some(frame)) = sender.poll_transmit() {
+            for frame in lossy(frame) {
+                receiver.receive(&frame);
+            }
+        }
+        while let Some(frame) = receiver.poll_transmit() {
+            for frame in lossy(frame) {
+                sender.receive(&frame);
+            }
+        }
+    }
+
+    // Generates a random image of the given size
+    fn image(rng: &mut StdRng, size: usize) -> Vec<u8> {
+        (0..size).map(|_| rng.random()).collect()
+    }
+
+    #[test]
+    fn test_transfer_lossy() {
+        let mut rng = StdRng::seed_from_u64(49);
+        for loss in [0.0, 0.05, 0.2] {
+            let clock = ManualClock::new();
+            let data = image(&mut rng, 10_000);
+            let config = TransferConfig {
+                max_retries: 50,
+                ..Default::default()
+            };
+            let mut sender = Sender::new(config, clock.clone(), 7, "fw.bin", data.clone());
+            let mut receiver = Receiver::new(TransferConfig {
+                mtu: 100,
+                ..Default::default()
+            });
+
+            let mut image = None;
+            while sender.state() != SenderState::Complete {
+                pump(&mut sender, &mut receiver, &mut rng, loss);
+                while let Some(event) = receiver.poll_event() {
+                    match event {
+                        Event::Offered(offer) => {
+                            assert_eq!((offer.name.as_str(), offer.size), ("fw.bin", 10_000));
+                            receiver.accept();
+                        }
+                        Event::Completed { image: data, .. } => image = Some(data),
+                        Event::Aborted { .. } => panic!("transfer aborted"),
+                    }
+                }
+                if let SenderState::Failed(err) = sender.state() {
+                    panic!("transfer failed: {err}");
+                }
+                clock.advance(Duration::from_millis(100));
+            }
+            assert_eq!(image.unwrap(), data);
+            if loss == 0.0 {
+                assert_eq!(sender.sent(), 10_000);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transfer_resume() {
+        let mut rng = StdRng::seed_from_u64(50);
+        let clock = ManualClock::new();
+        let data = image(&mut rng, 5_000);
+
+        // Get the transfer half way, then cut it off from the receiving end
+        let mut sender = Sender::new(
+            TransferConfig::default(),
+            clock.clone(),
+            1,
+            "a",
+            data.clone(),
+        );
+        let mut receiver = Receiver::new(TransferConfig::default());
+        pump(&mut sender, &mut receiver, &mut rng, 0.0);
+        assert!(matches!(receiver.poll_event(), Some(Event::Offered(_))));
+        receiver.accept();
+        pump(&mut sender, &mut receiver, &mut rng, 0.0);
+        pump(&mut sender, &mut receiver, &mut rng, 0.0);
+
+        receiver.abort();
+        let partial = receiver.partial().unwrap();
+        assert!(!partial.data.is_empty() && partial.data.len() < data.len());
+        pump(&mut sender, &mut receiver, &mut rng, 0.0);
+        assert_eq!(
+            sender.state(),
+            SenderState::Failed(TransferError::Aborted {
+                reason: AbortReason::Cancelled,
+                remote: true
+            })
+        );
+        assert!(sender.poll_transmit().is_err());
+
+        // A fresh transfer of the same image only sends the rest
+        let have = partial.data.len() as u64;
+        let mut sender = Sender::new(
+            TransferConfig::default(),
+            clock.clone(),
+            2,
+            "a",
+            data.clone(),
+        );
+        let mut receiver = Receiver::resume(TransferConfig::default(), partial);
+        let mut image = None;
+        while sender.state() != SenderState::Complete {
+            pump(&mut sender, &mut receiver, &mut rng, 0.0);
+            while let Some(event) = receiver.poll_event() {
+                match event {
+                    Event::Offered(_) => receiver.accept(),
+                    Event::Completed { image: data, .. } => image = Some(data),
+                    Event::Aborted { .. } => panic!("transfer aborted"),
+                }
+            }
+        }
+        assert_eq!(image.unwrap(), data);
+        assert_eq!(sender.sent(), data.len() as u64 - have);
+    }
+
+    #[test]
+    fn test_transfer_resume_complete() {
+        let mut rng = StdRng::seed_from_u64(52);
+        let data = image(&mut rng, 2_000);
+        let partial = Partial {
+            size: 2_000,
+            hash: Sha256::digest(&data).into(),
+            data: data.clone(),
+        };
+        // Losing the accept must not stall the sender on the completion
+        let mut sender = Sender::new(
+            TransferConfig::default(),
+            ManualClock::new(),
+            1,
+            "a",
+            data.clone(),
+        );
+        let mut receiver = Receiver::resume(TransferConfig::default(), partial.clone());
+        receiver.receive(&sender.poll_transmit().unwrap().unwrap());
+        assert!(matches!(receiver.poll_event(), Some(Event::Offered(_))));
+        receiver.accept();
+        receiver.poll_transmit().unwrap();
+        sender.receive(&receiver.poll_transmit().unwrap());
+        assert_eq!(sender.state(), SenderState::Complete);
+
+        // Same over a lossy channel, without ever sending any of the image
+        for id in 2..12 {
+            let clock = ManualClock::new();
+            let config = TransferConfig {
+                max_retries: 50,
+                ..Default::default()
+            };
+            let mut sender = Sender::new(config, clock.clone(), id, "a", data.clone());
+            let mut receiver = Receiver::resume(config, partial.clone());
+            let mut image = None;
+            while sender.state() != SenderState::Complete {
+                pump(&mut sender, &mut receiver, &mut rng, 0.2);
+                while let Some(event) = receiver.poll_event() {
+                    match event {
+                        Event::Offered(_) => receiver.accept(),
+                        Event::Completed { image: data, .. } => image = Some(data),
+                        Event::Aborted { .. } => panic!("transfer aborted"),
+                    }
+                }
+                if let SenderState::Failed(err) = sender.state() {
+                    panic!("transfer failed: {err}");
+                }
+                clock.advance(Duration::from_millis(100));
+            }
+            assert_eq!(image.unwrap(), data);
+            assert_eq!(sender.sent(), 0);
+        }
+    }
+
+    #[test]
+    fn test_transfer_reject_and_mismatch() {
+        let mut rng = StdRng::seed_from_u64(51);
+        let clock = ManualClock::new();
+        let data = image(&mut rng, 1_000);
+
+        // Rejected offers fail the sender
+        let mut sender = Sender::new(
+            TransferConfig::default(),
+            clock.clone(),
+            1,
+            "a",
+            data.clone(),
+        );
+        let mut receiver = Receiver::new(TransferConfig::default());
+        pump(&mut sender, &mut receiver, &mut rng, 0.0);
+        assert!(matches!(receiver.poll_event(), Some(Event::Offered(_))));
+        receiver.reject();
+        pump(&mut sender, &mut receiver, &mut rng, 0.0);
+        assert_eq!(
+            sender.poll_transmit(),
+            Err(TransferError::Aborted {
+                reason: AbortReason::Rejected,
+                remote: true
+            })
+        );
+
+        // A forged partial passes as resumable, but fails the final hash check
+        let mut forged = data.clone();
+        forged[10] ^= 1;
+        let partial = Partial {
+            size: 1_000,
+            hash: Sha256::digest(&data).into(),
+            data: forged[..500].to_vec(),
+        };
+        let mut sender = Sender::new(
+            TransferConfig::default(),
+            clock.clone(),
+            2,
+            "a",
+            data.clone(),
+        );
+        let mut receiver = Receiver::resume(TransferConfig::default(), partial);
+        pump(&mut sender, &mut receiver, &mut rng, 0.0);
+        receiver.poll_event();
+        receiver.accept();
+        for _ in 0..4 {
+            pump(&mut sender, &mut receiver, &mut rng, 0.0);
+        }
+        assert_eq!(
+            receiver.poll_event(),
+            Some(Event::Aborted {
+                reason: AbortReason::HashMismatch,
+                remote: false
+            })
+        );
+        assert_eq!(
+            sender.state(),
+            SenderState::Failed(TransferError::Aborted {
+                reason: AbortReason::HashMismatch,
+                remote: true
+            })
+        );
+
+        // Silent receivers exhaust the retries
+        let config = TransferConfig {
+            max_retries: 3,
+            ..Default::default()
+        };
+        let mut sender = Sender::new(config, clock.clone(), 3, "a", data);
+        for _ in 0..4 {
+            assert!(sender.poll_transmit().unwrap().is_some());
+            assert_eq!(sender.poll_transmit(), Ok(None));
+            clock.advance(config.timeout);
+        }
+        assert_eq!(
+            sender.poll_transmit(),
+            Err(TransferError::RetriesExhausted { retries: 3 })
+        );
+
+        // Even when every poll is a resend
+        let config = TransferConfig {
+            timeout: Duration::ZERO,
+            max_retries: 3,
+            ..Default::default()
+        };
+        let mut sender = Sender::new(config, ManualClock::new(), 4, "a", vec![1]);
+        for _ in 0..4 {
+            assert!(sender.poll_transmit().unwrap().is_some());
+        }
+        assert_eq!(
+            sender.poll_transmit(),
+            Err(TransferError::RetriesExhausted { retries: 3 })
+        );
+    }
+}